@@ -1,4 +1,5 @@
 mod ntp;
 mod packet;
 
-pub use packet::{Marshal, MarshalError};
+pub use ntp::{compact_to_duration, duration_to_compact, NtpClock, NtpTime};
+pub use packet::{Marshal, MarshalError, ParseError, Unmarshal};
@@ -1,4 +1,120 @@
-struct NtpTime {
-    seconds: u32,
-    fraction: u32,
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch (1970-01-01).
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// 64-bit NTP timestamp: seconds since 0h UTC on January 1900 and the fraction of the second in
+/// units of 1/2^32 seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NtpTime {
+    pub seconds: u32,
+    pub fraction: u32,
+}
+
+impl NtpTime {
+    pub fn new(seconds: u32, fraction: u32) -> Self {
+        Self { seconds, fraction }
+    }
+
+    /// Wallclock time, read from the system clock.
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    /// Times before the Unix epoch are clamped to it.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Self::from_duration(since_unix + Duration::from_secs(NTP_UNIX_OFFSET))
+    }
+
+    /// Builds a timestamp from a duration elapsed since the NTP epoch. The seconds wrap every 136 years,
+    /// as they do on the wire.
+    pub fn from_duration(duration: Duration) -> Self {
+        Self {
+            seconds: duration.as_secs() as u32,
+            fraction: ((duration.subsec_nanos() as u64) << 32).div_ceil(1_000_000_000) as u32,
+        }
+    }
+
+    /// Duration elapsed since the NTP epoch (modulo the 136 years era).
+    pub fn as_duration(&self) -> Duration {
+        let nanos = (self.fraction as u64 * 1_000_000_000) >> 32;
+        Duration::new(self.seconds as u64, nanos as u32)
+    }
+
+    /// The middle 32 bits of the timestamp, as carried in the LSR field of report blocks: the 16 low bits
+    /// of the seconds and the 16 high bits of the fraction.
+    pub fn compact(&self) -> u32 {
+        (self.seconds << 16) | (self.fraction >> 16)
+    }
+
+    /// Duration elapsed between `earlier` and this timestamp, zero if `earlier` is later.
+    pub fn saturating_duration_since(&self, earlier: NtpTime) -> Duration {
+        self.as_duration().saturating_sub(earlier.as_duration())
+    }
+}
+
+impl From<u64> for NtpTime {
+    fn from(value: u64) -> Self {
+        Self {
+            seconds: (value >> 32) as u32,
+            fraction: value as u32,
+        }
+    }
+}
+
+impl From<NtpTime> for u64 {
+    fn from(value: NtpTime) -> Self {
+        ((value.seconds as u64) << 32) | value.fraction as u64
+    }
+}
+
+impl std::ops::Add<Duration> for NtpTime {
+    type Output = NtpTime;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        NtpTime::from_duration(self.as_duration() + rhs)
+    }
+}
+
+/// Converts a duration to the compact NTP format, in units of 1/65536 seconds, as used by the DLSR field
+/// of report blocks. Durations that do not fit saturate to `u32::MAX`.
+pub fn duration_to_compact(duration: Duration) -> u32 {
+    let units = (duration.as_nanos() << 16) / 1_000_000_000;
+    units.min(u32::MAX as u128) as u32
+}
+
+/// Converts a value in units of 1/65536 seconds back to a duration.
+pub fn compact_to_duration(compact: u32) -> Duration {
+    Duration::from_nanos((compact as u64 * 1_000_000_000) >> 16)
+}
+
+/// Produces NTP timestamps from a monotonic clock. The wallclock is sampled once when the clock is created,
+/// later timestamps are derived from the elapsed [`Instant`], so they never jump backwards when the system
+/// clock is adjusted.
+#[derive(Debug, Clone, Copy)]
+pub struct NtpClock {
+    anchor: Instant,
+    anchor_time: NtpTime,
+}
+
+impl NtpClock {
+    /// Anchors the clock on the current wallclock time.
+    pub fn new(now: Instant) -> Self {
+        Self::with_anchor(now, NtpTime::now())
+    }
+
+    /// Anchors the clock on an arbitrary NTP time. A sender without wallclock may use
+    /// `NtpTime::default()` to produce relative timestamps, akin to a system uptime.
+    pub fn with_anchor(anchor: Instant, anchor_time: NtpTime) -> Self {
+        Self {
+            anchor,
+            anchor_time,
+        }
+    }
+
+    /// NTP timestamp corresponding to `now`. Instants earlier than the anchor map to the anchor time.
+    pub fn ntp_time(&self, now: Instant) -> NtpTime {
+        self.anchor_time + now.saturating_duration_since(self.anchor)
+    }
 }
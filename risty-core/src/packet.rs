@@ -10,3 +10,62 @@ pub trait Marshal {
     fn marshal(&self, buf: &mut [u8]) -> Result<usize, MarshalError>;
     fn marshal_size(&self) -> usize;
 }
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("buffer too short: expected at least {expected} bytes, got {actual}")]
+    Truncated { expected: usize, actual: usize },
+
+    #[error("unsupported version {0}")]
+    BadVersion(u8),
+
+    #[error("invalid length field {0}")]
+    BadLength(u16),
+
+    #[error("unknown packet type {0}")]
+    UnknownPacketType(u8),
+
+    #[error("unexpected packet type {0}")]
+    UnexpectedPacketType(u8),
+
+    #[error("unexpected packet subtype {0}")]
+    UnexpectedSubtype(u8),
+
+    #[error("unexpected application name {0:#010x}")]
+    UnexpectedName(u32),
+
+    #[error("unknown protocol type {0:#06x}")]
+    UnknownProtocolType(u16),
+
+    #[error("bad checksum")]
+    BadChecksum,
+
+    #[error("unexpected port {0}")]
+    UnexpectedPort(u16),
+
+    #[error("invalid compound packet: {0}")]
+    InvalidCompound(&'static str),
+
+    #[error("failed to bit unpack struct")]
+    StructUnpackedFailure(#[from] packed_struct::PackingError),
+}
+
+impl ParseError {
+    /// Returns an error if `buf` holds fewer than `expected` bytes.
+    pub fn check_len(buf: &[u8], expected: usize) -> Result<(), ParseError> {
+        if buf.len() < expected {
+            Err(ParseError::Truncated {
+                expected,
+                actual: buf.len(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Counterpart of [`Marshal`]: builds a value from the bytes received on the wire.
+/// The lifetime allows implementors to borrow from the input buffer instead of copying.
+pub trait Unmarshal<'a>: Sized {
+    fn unmarshal(buf: &'a [u8]) -> Result<Self, ParseError>;
+}
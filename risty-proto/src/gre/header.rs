@@ -0,0 +1,93 @@
+use packed_struct::prelude::*;
+use risty_core::ParseError;
+
+/// GRE version of RFC 2784, the only one RIST uses.
+pub(crate) const VERSION: u8 = 0;
+
+/// Size in bytes of the fixed part of the GRE header.
+pub(crate) const HEADER_SIZE: usize = 4;
+
+/// Type of the packet carried by a GRE packet, an Ethertype.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolType {
+    /// Full datagram mode: an IPv4 packet with its UDP header.
+    Ipv4 = 0x0800,
+
+    /// Full datagram mode: an IPv6 packet with its UDP header.
+    Ipv6 = 0x86DD,
+
+    /// Keep-alive message of the Main Profile.
+    KeepAlive = 0x88B5,
+
+    /// Reduced overhead mode: the virtual UDP ports followed by a RTP or RTCP packet.
+    ReducedOverhead = 0x88B6,
+}
+
+impl TryFrom<u16> for ProtocolType {
+    type Error = ParseError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0x0800 => Ok(ProtocolType::Ipv4),
+            0x86DD => Ok(ProtocolType::Ipv6),
+            0x88B5 => Ok(ProtocolType::KeepAlive),
+            0x88B6 => Ok(ProtocolType::ReducedOverhead),
+            other => Err(ParseError::UnknownProtocolType(other)),
+        }
+    }
+}
+
+/// Fixed part of the GRE header. The optional fields it announces follow it, in order.
+#[derive(PackedStruct, Debug, Clone, PartialEq)]
+#[packed_struct(bit_numbering = "msb0")]
+pub struct Header {
+    /// If set, the checksum and reserved1 fields are present.
+    #[packed_field(bits = "0")]
+    pub checksum_present: bool,
+
+    /// Shall be 0.
+    #[packed_field(bits = "1")]
+    pub reserved: bool,
+
+    /// If set, the key field is present.
+    #[packed_field(bits = "2")]
+    pub key_present: bool,
+
+    /// If set, the sequence number field is present.
+    #[packed_field(bits = "3")]
+    pub sequence_number_present: bool,
+
+    /// Shall be 0.
+    #[packed_field(bits = "4..=12", endian = "msb")]
+    pub reserved0: Integer<u16, packed_bits::Bits<9>>,
+
+    /// Shall be 0.
+    #[packed_field(bits = "13..=15")]
+    pub version: Integer<u8, packed_bits::Bits<3>>,
+
+    /// Type of the payload, see [`ProtocolType`].
+    #[packed_field(bits = "16..=31", endian = "msb")]
+    pub protocol_type: u16,
+}
+
+impl Header {
+    pub fn new(protocol_type: ProtocolType) -> Self {
+        Self {
+            checksum_present: false,
+            reserved: false,
+            key_present: false,
+            sequence_number_present: false,
+            reserved0: 0.into(),
+            version: VERSION.into(),
+            protocol_type: protocol_type as u16,
+        }
+    }
+
+    /// Size in bytes of the header with the optional fields it announces.
+    pub fn size(&self) -> usize {
+        HEADER_SIZE
+            + 4 * (self.checksum_present as usize
+                + self.key_present as usize
+                + self.sequence_number_present as usize)
+    }
+}
@@ -0,0 +1,52 @@
+use risty_core::{Marshal, MarshalError, ParseError, Unmarshal};
+
+/// Size in bytes of the fixed part of the keep-alive message.
+const HEADER_SIZE: usize = 8;
+
+/// Keep-alive message, sent periodically by both ends of a tunnel so that it stays open through firewalls and
+/// NATs even when no media flows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeepAlive<'a> {
+    /// MAC address of the interface the tunnel runs on, all zeros if unknown.
+    pub mac: [u8; 6],
+
+    /// Features supported by the sending end, as defined by TR-06-2.
+    pub capabilities: u16,
+
+    /// Optional information about the sending end, JSON encoded.
+    pub info: &'a [u8],
+}
+
+impl<'a> KeepAlive<'a> {
+    pub fn new(mac: [u8; 6], capabilities: u16) -> Self {
+        Self {
+            mac,
+            capabilities,
+            info: &[],
+        }
+    }
+}
+
+impl Marshal for KeepAlive<'_> {
+    fn marshal(&self, buf: &mut [u8]) -> Result<usize, MarshalError> {
+        buf[0..6].copy_from_slice(&self.mac);
+        buf[6..8].copy_from_slice(&self.capabilities.to_be_bytes());
+        buf[HEADER_SIZE..self.marshal_size()].copy_from_slice(self.info);
+        Ok(self.marshal_size())
+    }
+
+    fn marshal_size(&self) -> usize {
+        HEADER_SIZE + self.info.len()
+    }
+}
+
+impl<'a> Unmarshal<'a> for KeepAlive<'a> {
+    fn unmarshal(buf: &'a [u8]) -> Result<Self, ParseError> {
+        ParseError::check_len(buf, HEADER_SIZE)?;
+        Ok(Self {
+            mac: buf[0..6].try_into().unwrap(),
+            capabilities: u16::from_be_bytes([buf[6], buf[7]]),
+            info: &buf[HEADER_SIZE..],
+        })
+    }
+}
@@ -0,0 +1,8 @@
+//! GRE over UDP encapsulation of the RIST Main Profile (VSF TR-06-2). The RTP and RTCP packets of a flow are
+//! multiplexed in a single UDP flow: each one is wrapped in a GRE packet (RFC 2784, RFC 2890), carrying the
+//! virtual UDP ports of the reduced overhead mode that tell them apart.
+
+pub mod header;
+pub mod keepalive;
+pub mod packet;
+pub mod reduced;
@@ -0,0 +1,129 @@
+use packed_struct::prelude::*;
+use risty_core::{Marshal, MarshalError, ParseError, Unmarshal};
+
+use super::header::{Header, ProtocolType, HEADER_SIZE, VERSION};
+
+/// GRE packet. The payload is borrowed from the buffer it was parsed from or from the application.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrePacket<'a> {
+    pub header: Header,
+
+    /// Identifies a flow within the tunnel. RIST uses it to carry the nonce of encrypted flows.
+    pub key: Option<u32>,
+
+    pub sequence_number: Option<u32>,
+
+    pub payload: &'a [u8],
+}
+
+impl<'a> GrePacket<'a> {
+    pub fn new(protocol_type: ProtocolType, payload: &'a [u8]) -> Self {
+        Self {
+            header: Header::new(protocol_type),
+            key: None,
+            sequence_number: None,
+            payload,
+        }
+    }
+
+    /// The checksum is computed when the packet is marshalled, and verified when it is parsed.
+    pub fn with_checksum(mut self, checksum: bool) -> Self {
+        self.header.checksum_present = checksum;
+        self
+    }
+
+    pub fn with_key(mut self, key: Option<u32>) -> Self {
+        self.header.key_present = key.is_some();
+        self.key = key;
+        self
+    }
+
+    pub fn with_sequence_number(mut self, sequence_number: Option<u32>) -> Self {
+        self.header.sequence_number_present = sequence_number.is_some();
+        self.sequence_number = sequence_number;
+        self
+    }
+
+    pub fn protocol_type(&self) -> Result<ProtocolType, ParseError> {
+        ProtocolType::try_from(self.header.protocol_type)
+    }
+}
+
+impl Marshal for GrePacket<'_> {
+    fn marshal(&self, buf: &mut [u8]) -> Result<usize, MarshalError> {
+        self.header.pack_to_slice(&mut buf[0..HEADER_SIZE])?;
+
+        let mut offset = HEADER_SIZE;
+        if self.header.checksum_present {
+            // Zeroed while the checksum is computed, the reserved1 field stays at 0.
+            buf[offset..offset + 4].fill(0);
+            offset += 4;
+        }
+        if let Some(key) = self.key {
+            buf[offset..offset + 4].copy_from_slice(&key.to_be_bytes());
+            offset += 4;
+        }
+        if let Some(sequence_number) = self.sequence_number {
+            buf[offset..offset + 4].copy_from_slice(&sequence_number.to_be_bytes());
+            offset += 4;
+        }
+        buf[offset..offset + self.payload.len()].copy_from_slice(self.payload);
+
+        let size = self.marshal_size();
+        if self.header.checksum_present {
+            let checksum = checksum(&buf[..size]);
+            buf[HEADER_SIZE..HEADER_SIZE + 2].copy_from_slice(&checksum.to_be_bytes());
+        }
+        Ok(size)
+    }
+
+    fn marshal_size(&self) -> usize {
+        self.header.size() + self.payload.len()
+    }
+}
+
+impl<'a> Unmarshal<'a> for GrePacket<'a> {
+    fn unmarshal(buf: &'a [u8]) -> Result<Self, ParseError> {
+        ParseError::check_len(buf, HEADER_SIZE)?;
+        let header = Header::unpack_from_slice(&buf[..HEADER_SIZE])?;
+        if u8::from(header.version) != VERSION {
+            return Err(ParseError::BadVersion(header.version.into()));
+        }
+        ParseError::check_len(buf, header.size())?;
+
+        // The checksum covers the whole packet, a valid one sums up to 0.
+        if header.checksum_present && checksum(buf) != 0 {
+            return Err(ParseError::BadChecksum);
+        }
+
+        let mut offset = HEADER_SIZE + 4 * header.checksum_present as usize;
+        let mut read_u32 = |present: bool| {
+            present.then(|| {
+                let value = u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap());
+                offset += 4;
+                value
+            })
+        };
+        let key = read_u32(header.key_present);
+        let sequence_number = read_u32(header.sequence_number_present);
+
+        Ok(Self {
+            payload: &buf[header.size()..],
+            header,
+            key,
+            sequence_number,
+        })
+    }
+}
+
+/// One's complement of the one's complement sum of the 16-bit words of `bytes`, as in IP (RFC 1071).
+fn checksum(bytes: &[u8]) -> u16 {
+    let mut sum = bytes
+        .chunks(2)
+        .map(|word| u32::from(word[0]) << 8 | u32::from(*word.get(1).unwrap_or(&0)))
+        .sum::<u32>();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
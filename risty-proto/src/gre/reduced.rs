@@ -0,0 +1,48 @@
+use risty_core::{Marshal, MarshalError, ParseError, Unmarshal};
+
+/// Size in bytes of the virtual UDP ports.
+const HEADER_SIZE: usize = 4;
+
+/// Payload of a GRE packet in reduced overhead mode: the UDP ports the packet would have been sent from and to
+/// without the tunnel, followed by the RTP or RTCP packet. As in the Simple Profile, RTP packets go to an even
+/// destination port P and RTCP packets to P+1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReducedOverhead<'a> {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub payload: &'a [u8],
+}
+
+impl<'a> ReducedOverhead<'a> {
+    pub fn new(source_port: u16, destination_port: u16, payload: &'a [u8]) -> Self {
+        Self {
+            source_port,
+            destination_port,
+            payload,
+        }
+    }
+}
+
+impl Marshal for ReducedOverhead<'_> {
+    fn marshal(&self, buf: &mut [u8]) -> Result<usize, MarshalError> {
+        buf[0..2].copy_from_slice(&self.source_port.to_be_bytes());
+        buf[2..4].copy_from_slice(&self.destination_port.to_be_bytes());
+        buf[HEADER_SIZE..self.marshal_size()].copy_from_slice(self.payload);
+        Ok(self.marshal_size())
+    }
+
+    fn marshal_size(&self) -> usize {
+        HEADER_SIZE + self.payload.len()
+    }
+}
+
+impl<'a> Unmarshal<'a> for ReducedOverhead<'a> {
+    fn unmarshal(buf: &'a [u8]) -> Result<Self, ParseError> {
+        ParseError::check_len(buf, HEADER_SIZE)?;
+        Ok(Self {
+            source_port: u16::from_be_bytes([buf[0], buf[1]]),
+            destination_port: u16::from_be_bytes([buf[2], buf[3]]),
+            payload: &buf[HEADER_SIZE..],
+        })
+    }
+}
@@ -1 +1,2 @@
-mod rtp;
+pub mod gre;
+pub mod rtp;
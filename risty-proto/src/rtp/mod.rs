@@ -1 +1,2 @@
-mod rtcp;
+pub mod packet;
+pub mod rtcp;
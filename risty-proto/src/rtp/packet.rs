@@ -0,0 +1,263 @@
+use packed_struct::prelude::*;
+use risty_core::{Marshal, MarshalError, ParseError, Unmarshal};
+
+pub(crate) const VERSION: u8 = 2;
+
+/// Size in bytes of the fixed part of the RTP header.
+const HEADER_SIZE: usize = 12;
+
+/// Fixed part of the RTP header, common to every RTP data packet.
+#[derive(PackedStruct, Debug, Clone, PartialEq)]
+#[packed_struct(bit_numbering = "msb0")]
+pub struct Header {
+    /// Identifies the version of RTP. RIST packets shall have V=2.
+    #[packed_field(bits = "0..=1")]
+    pub version: Integer<u8, packed_bits::Bits<2>>,
+
+    /// If set, the packet contains one or more additional padding octets at the end which are not
+    /// part of the payload. The last octet of the padding contains a count of how many padding octets
+    /// should be ignored, including itself.
+    #[packed_field(bits = "2")]
+    pub padding: bool,
+
+    /// If set, the fixed header is followed by exactly one header extension.
+    #[packed_field(bits = "3")]
+    pub extension: bool,
+
+    /// The number of CSRC identifiers that follow the fixed header.
+    #[packed_field(bits = "4..=7")]
+    pub csrc_count: Integer<u8, packed_bits::Bits<4>>,
+
+    /// The interpretation of the marker is defined by a profile. For MPEG-2 TS payloads it is unused
+    /// and shall be set to zero.
+    #[packed_field(bits = "8")]
+    pub marker: bool,
+
+    /// Identifies the format of the RTP payload. MPEG-2 TS payloads use PT=33.
+    #[packed_field(bits = "9..=15")]
+    pub payload_type: Integer<u8, packed_bits::Bits<7>>,
+
+    /// Increments by one for each RTP data packet sent. RIST receivers use it to detect packet loss
+    /// and to restore packet sequence.
+    #[packed_field(bits = "16..=31", endian = "msb")]
+    pub sequence_number: u16,
+
+    /// Reflects the sampling instant of the first octet in the RTP data packet.
+    #[packed_field(bits = "32..=63", endian = "msb")]
+    pub timestamp: u32,
+
+    /// Identifies the synchronization source. The LSB is used to differentiate between original
+    /// packets (LSB=0) and retransmitted packets (LSB=1).
+    #[packed_field(bits = "64..=95", endian = "msb")]
+    pub ssrc: u32,
+}
+
+/// Profile specific header extension following the fixed header and CSRC list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderExtension<'a> {
+    /// Defined by profile, allows multiple interoperating implementations to each experiment independently.
+    pub profile: u16,
+
+    /// Extension content. Its length is announced in 32-bit words, data of other lengths is padded with zeros
+    /// when marshalled.
+    pub data: &'a [u8],
+}
+
+impl HeaderExtension<'_> {
+    /// Length of the data rounded up to a whole number of 32-bit words.
+    fn padded_len(&self) -> usize {
+        self.data.len().next_multiple_of(4)
+    }
+}
+
+/// RTP data packet carrying the media payload. The payload and header extension are borrowed from
+/// the buffer they were parsed from or from the application.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtpPacket<'a> {
+    pub header: Header,
+
+    /// Contributing sources for the payload contained in this packet.
+    pub csrcs: Vec<u32>,
+
+    pub extension: Option<HeaderExtension<'a>>,
+
+    pub payload: &'a [u8],
+
+    /// Number of padding bytes appended after the payload, including the count byte itself.
+    pub padding_size: u8,
+}
+
+impl<'a> RtpPacket<'a> {
+    pub fn new(
+        payload_type: u8,
+        sequence_number: u16,
+        timestamp: u32,
+        ssrc: u32,
+        payload: &'a [u8],
+    ) -> Self {
+        Self {
+            header: Header {
+                version: VERSION.into(),
+                padding: false,
+                extension: false,
+                csrc_count: 0.into(),
+                marker: false,
+                payload_type: payload_type.into(),
+                sequence_number,
+                timestamp,
+                ssrc,
+            },
+            csrcs: vec![],
+            extension: None,
+            payload,
+            padding_size: 0,
+        }
+    }
+
+    pub fn with_marker(mut self, marker: bool) -> Self {
+        self.header.marker = marker;
+        self
+    }
+
+    /// At most 15 CSRC identifiers can be carried, any extra identifier is dropped.
+    pub fn with_csrcs(mut self, mut csrcs: Vec<u32>) -> Self {
+        csrcs.truncate(15);
+        self.header.csrc_count = (csrcs.len() as u8).into();
+        self.csrcs = csrcs;
+        self
+    }
+
+    pub fn with_extension(mut self, extension: HeaderExtension<'a>) -> Self {
+        self.header.extension = true;
+        self.extension = Some(extension);
+        self
+    }
+
+    /// `padding_size` is the total number of bytes appended after the payload, 0 disables padding.
+    pub fn with_padding(mut self, padding_size: u8) -> Self {
+        self.header.padding = padding_size > 0;
+        self.padding_size = padding_size;
+        self
+    }
+
+    fn extension_size(&self) -> usize {
+        self.extension
+            .as_ref()
+            .map_or(0, |extension| 4 + extension.padded_len())
+    }
+}
+
+impl Marshal for RtpPacket<'_> {
+    fn marshal(&self, buf: &mut [u8]) -> Result<usize, MarshalError> {
+        self.header.pack_to_slice(&mut buf[0..HEADER_SIZE])?;
+
+        let mut offset = HEADER_SIZE;
+        for csrc in &self.csrcs {
+            buf[offset..offset + 4].copy_from_slice(&csrc.to_be_bytes());
+            offset += 4;
+        }
+
+        if let Some(extension) = &self.extension {
+            let padded_len = extension.padded_len();
+            let length = (padded_len / 4) as u16;
+            buf[offset..offset + 2].copy_from_slice(&extension.profile.to_be_bytes());
+            buf[offset + 2..offset + 4].copy_from_slice(&length.to_be_bytes());
+            let data_start = offset + 4;
+            buf[data_start..data_start + extension.data.len()].copy_from_slice(extension.data);
+            buf[data_start + extension.data.len()..data_start + padded_len].fill(0);
+            offset = data_start + padded_len;
+        }
+
+        buf[offset..offset + self.payload.len()].copy_from_slice(self.payload);
+        offset += self.payload.len();
+
+        if self.padding_size > 0 {
+            let padding_end = offset + self.padding_size as usize;
+            buf[offset..padding_end - 1].fill(0);
+            buf[padding_end - 1] = self.padding_size;
+        }
+
+        Ok(self.marshal_size())
+    }
+
+    fn marshal_size(&self) -> usize {
+        HEADER_SIZE
+            + 4 * self.csrcs.len()
+            + self.extension_size()
+            + self.payload.len()
+            + self.padding_size as usize
+    }
+}
+
+impl<'a> Unmarshal<'a> for RtpPacket<'a> {
+    fn unmarshal(buf: &'a [u8]) -> Result<Self, ParseError> {
+        ParseError::check_len(buf, HEADER_SIZE)?;
+        let header = Header::unpack_from_slice(&buf[0..HEADER_SIZE])?;
+        if u8::from(header.version) != VERSION {
+            return Err(ParseError::BadVersion(header.version.into()));
+        }
+
+        let csrc_count = u8::from(header.csrc_count) as usize;
+        let mut offset = HEADER_SIZE + 4 * csrc_count;
+        ParseError::check_len(buf, offset)?;
+        let csrcs = buf[HEADER_SIZE..offset]
+            .chunks_exact(4)
+            .map(|csrc| u32::from_be_bytes(csrc.try_into().unwrap()))
+            .collect();
+
+        let extension = if header.extension {
+            ParseError::check_len(buf, offset + 4)?;
+            let profile = u16::from_be_bytes([buf[offset], buf[offset + 1]]);
+            let length = u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) as usize;
+            let data_start = offset + 4;
+            offset = data_start + 4 * length;
+            ParseError::check_len(buf, offset)?;
+            Some(HeaderExtension {
+                profile,
+                data: &buf[data_start..offset],
+            })
+        } else {
+            None
+        };
+
+        let padding_size = if header.padding {
+            let padding_size = buf[buf.len() - 1];
+            if padding_size == 0 || offset + padding_size as usize > buf.len() {
+                return Err(ParseError::BadLength(padding_size as u16));
+            }
+            padding_size
+        } else {
+            0
+        };
+
+        Ok(Self {
+            header,
+            csrcs,
+            extension,
+            payload: &buf[offset..buf.len() - padding_size as usize],
+            padding_size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extension_is_padded_to_32_bit_words() {
+        let packet = RtpPacket::new(33, 1, 2, 3, &[0xAA; 4]).with_extension(HeaderExtension {
+            profile: 0xBEDE,
+            data: &[1, 2, 3, 4, 5],
+        });
+        let mut buf = vec![0xFF; packet.marshal_size()];
+        packet.marshal(&mut buf).unwrap();
+
+        assert_eq!(buf.len(), HEADER_SIZE + 4 + 8 + 4);
+        assert_eq!(&buf[HEADER_SIZE..HEADER_SIZE + 4], &[0xBE, 0xDE, 0, 2]);
+
+        let parsed = RtpPacket::unmarshal(&buf).unwrap();
+        assert_eq!(parsed.extension.unwrap().data, &[1, 2, 3, 4, 5, 0, 0, 0]);
+        assert_eq!(parsed.payload, &[0xAA; 4]);
+    }
+}
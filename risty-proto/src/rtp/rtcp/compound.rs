@@ -0,0 +1,181 @@
+use std::borrow::Cow;
+
+use super::feedback::nack::GenericNack;
+use super::feedback::rist::{RangeBasedNACK, RttEcho};
+use super::header::{Header, PacketType};
+use super::packet::RtcpPacket;
+use super::receiver_report::ReceiverReport;
+use super::sdes::Sdes;
+use super::sender_report::SenderReport;
+use risty_core::{Marshal, MarshalError, ParseError, Unmarshal};
+
+/// Report opening a compound RTCP packet.
+pub enum Report<'a> {
+    Sender(&'a SenderReport),
+    Receiver(&'a ReceiverReport),
+}
+
+/// Optional packets that may follow the SDES packet in a compound RTCP packet.
+pub enum Feedback<'a> {
+    GenericNack(&'a GenericNack<'a>),
+    RangeBasedNACK(&'a RangeBasedNACK<'a>),
+    RttEcho(&'a RttEcho),
+}
+
+impl Feedback<'_> {
+    fn as_marshal(&self) -> &dyn Marshal {
+        match self {
+            Feedback::GenericNack(nack) => *nack,
+            Feedback::RangeBasedNACK(nack) => *nack,
+            Feedback::RttEcho(echo) => *echo,
+        }
+    }
+}
+
+/// RIST endpoints shall send RTCP packets as compound packets (section 5.2.1): a SR or RR packet, followed
+/// by a SDES packet carrying the CNAME, followed by any number of NACK and RTT echo packets. All the
+/// sub-packets are stored contiguously in a single buffer, ready to be sent in one datagram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompoundRtcp<'a> {
+    buf: Cow<'a, [u8]>,
+}
+
+impl<'a> CompoundRtcp<'a> {
+    pub fn builder(report: Report<'a>, sdes: &'a Sdes) -> CompoundRtcpBuilder<'a> {
+        CompoundRtcpBuilder {
+            report,
+            sdes,
+            feedbacks: vec![],
+        }
+    }
+
+    /// The whole compound packet as it goes on the wire.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Iterates over the sub-packets, yielding the header and the bytes of each of them.
+    pub fn iter(&self) -> SubPackets<'_> {
+        SubPackets { buf: &self.buf }
+    }
+
+    /// Iterates over the sub-packets, parsed according to their packet type.
+    pub fn packets(&self) -> impl Iterator<Item = Result<RtcpPacket<'_>, ParseError>> {
+        self.iter()
+            .map(|sub_packet| sub_packet.and_then(|(_, bytes)| RtcpPacket::unmarshal(bytes)))
+    }
+}
+
+impl<'a> Unmarshal<'a> for CompoundRtcp<'a> {
+    fn unmarshal(buf: &'a [u8]) -> Result<Self, ParseError> {
+        let mut sub_packets = SubPackets { buf };
+
+        match sub_packets.next().transpose()? {
+            Some((header, _))
+                if header.packet_type == PacketType::SenderReport as u8
+                    || header.packet_type == PacketType::ReceiverReport as u8 => {}
+            Some(_) => return Err(ParseError::InvalidCompound("first packet must be SR or RR")),
+            None => return Err(ParseError::InvalidCompound("empty compound packet")),
+        }
+
+        match sub_packets.next().transpose()? {
+            Some((header, _)) if header.packet_type == PacketType::Sdes as u8 => {}
+            _ => return Err(ParseError::InvalidCompound("SDES must follow the report")),
+        }
+
+        for sub_packet in sub_packets {
+            let (header, _) = sub_packet?;
+            if header.packet_type != PacketType::App as u8
+                && header.packet_type != PacketType::Feedback as u8
+            {
+                return Err(ParseError::InvalidCompound(
+                    "only NACK and RTT echo packets may follow SDES",
+                ));
+            }
+        }
+
+        Ok(Self {
+            buf: Cow::Borrowed(buf),
+        })
+    }
+}
+
+/// Iterator over the sub-packets of a compound RTCP packet.
+pub struct SubPackets<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> SubPackets<'a> {
+    /// Iterates over the RTCP packets stored back to back in `buf`, without checking their ordering.
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+}
+
+impl<'a> Iterator for SubPackets<'a> {
+    type Item = Result<(Header, &'a [u8]), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+
+        match Header::unmarshal(self.buf) {
+            Ok(header) => {
+                let (packet, rest) = self.buf.split_at(header.packet_size());
+                self.buf = rest;
+                Some(Ok((header, packet)))
+            }
+            Err(err) => {
+                self.buf = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+pub struct CompoundRtcpBuilder<'a> {
+    report: Report<'a>,
+    sdes: &'a Sdes,
+    feedbacks: Vec<Feedback<'a>>,
+}
+
+impl<'a> CompoundRtcpBuilder<'a> {
+    /// Appends a NACK or RTT echo packet after the SDES packet.
+    pub fn push(mut self, feedback: Feedback<'a>) -> Self {
+        self.feedbacks.push(feedback);
+        self
+    }
+
+    pub fn build(&self) -> Result<CompoundRtcp<'static>, MarshalError> {
+        let mut buf = vec![0; self.marshal_size()];
+        self.marshal(&mut buf)?;
+        Ok(CompoundRtcp {
+            buf: Cow::Owned(buf),
+        })
+    }
+
+    fn packets(&self) -> impl Iterator<Item = &dyn Marshal> {
+        let report: &dyn Marshal = match self.report {
+            Report::Sender(sr) => sr,
+            Report::Receiver(rr) => rr,
+        };
+        [report, self.sdes as &dyn Marshal]
+            .into_iter()
+            .chain(self.feedbacks.iter().map(Feedback::as_marshal))
+    }
+}
+
+impl Marshal for CompoundRtcpBuilder<'_> {
+    fn marshal(&self, buf: &mut [u8]) -> Result<usize, MarshalError> {
+        let mut offset = 0;
+        for packet in self.packets() {
+            offset += packet.marshal(&mut buf[offset..])?;
+        }
+        Ok(offset)
+    }
+
+    fn marshal_size(&self) -> usize {
+        self.packets().map(|packet| packet.marshal_size()).sum()
+    }
+}
@@ -0,0 +1,252 @@
+use super::nack::{Fci, GenericNack};
+use super::rist::{PacketRangeRequest, RangeBasedNACK};
+use crate::rtp::rtcp::compound::Feedback;
+
+/// Size of the header of both NACK packets: RTCP header, then two 32-bit words.
+const NACK_HEADER_SIZE: usize = 12;
+
+/// Size of a FCI or of a packet range request.
+const ENTRY_SIZE: usize = 4;
+
+/// NACK formats the peer is able to process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NackFormats {
+    /// Generic NACK messages only.
+    Bitmask,
+
+    /// Range-based NACK messages only.
+    Range,
+
+    /// Both formats, the smallest encoding is used.
+    Any,
+}
+
+/// A NACK packet produced by the [`NackEncoder`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nack {
+    Bitmask(GenericNack<'static>),
+    Range(RangeBasedNACK<'static>),
+}
+
+impl Nack {
+    /// The packet, to be pushed in a compound RTCP packet.
+    pub fn as_feedback(&self) -> Feedback<'_> {
+        match self {
+            Nack::Bitmask(nack) => Feedback::GenericNack(nack),
+            Nack::Range(nack) => Feedback::RangeBasedNACK(nack),
+        }
+    }
+}
+
+/// Turns a set of lost sequence numbers into NACK packets. Bitmask FCIs are compact when the losses are
+/// sparse, while a single packet range request covers a whole burst: both encodings are computed and the one
+/// with the fewest entries is kept, among the formats the peer supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NackEncoder {
+    formats: NackFormats,
+
+    /// Largest NACK packet allowed, in bytes.
+    max_packet_size: usize,
+}
+
+impl NackEncoder {
+    /// - `max_packet_size` is the room left for one NACK packet in the datagram, that is the MTU minus the
+    ///   IP and UDP headers and the report and SDES packets of the compound packet. Larger encodings are
+    ///   split in several NACK packets.
+    pub fn new(formats: NackFormats, max_packet_size: usize) -> Self {
+        Self {
+            formats,
+            max_packet_size,
+        }
+    }
+
+    /// Encodes the retransmission requests of packets sent by `ssrc`.
+    /// - `lost` holds the sequence numbers in sending order, without duplicates.
+    pub fn encode(&self, ssrc: u32, lost: &[u16]) -> Vec<Nack> {
+        if lost.is_empty() {
+            return vec![];
+        }
+
+        let entries_per_packet =
+            (self.max_packet_size.saturating_sub(NACK_HEADER_SIZE) / ENTRY_SIZE).max(1);
+
+        let fcis = bitmask_fcis(lost);
+        let ranges = packet_ranges(lost);
+        let use_ranges = match self.formats {
+            NackFormats::Bitmask => false,
+            NackFormats::Range => true,
+            // Generic NACK is the standard RTP feedback message, it wins ties.
+            NackFormats::Any => ranges.len() < fcis.len(),
+        };
+
+        if use_ranges {
+            ranges
+                .chunks(entries_per_packet)
+                .map(|chunk| Nack::Range(RangeBasedNACK::new(ssrc, chunk.to_vec())))
+                .collect()
+        } else {
+            fcis.chunks(entries_per_packet)
+                .map(|chunk| Nack::Bitmask(GenericNack::new(ssrc, chunk.to_vec())))
+                .collect()
+        }
+    }
+}
+
+/// Each FCI reports the packet in its PID and up to 16 packets following it.
+fn bitmask_fcis(lost: &[u16]) -> Vec<Fci> {
+    let mut fcis: Vec<Fci> = vec![];
+    for &seq in lost {
+        if let Some(fci) = fcis.last_mut() {
+            let distance = seq.wrapping_sub(fci.pid);
+            if (1..=16).contains(&distance) {
+                fci.blp |= 1 << (distance - 1);
+                continue;
+            }
+        }
+        fcis.push(Fci { pid: seq, blp: 0 });
+    }
+    fcis
+}
+
+/// Each packet range request reports a run of consecutive packets.
+fn packet_ranges(lost: &[u16]) -> Vec<PacketRangeRequest> {
+    let mut ranges: Vec<PacketRangeRequest> = vec![];
+    for &seq in lost {
+        if let Some(range) = ranges.last_mut() {
+            let next = range
+                .seq_start
+                .wrapping_add(range.nb_consecutive)
+                .wrapping_add(1);
+            if seq == next && range.nb_consecutive < u16::MAX {
+                range.nb_consecutive += 1;
+                continue;
+            }
+        }
+        ranges.push(PacketRangeRequest {
+            seq_start: seq,
+            nb_consecutive: 0,
+        });
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use risty_core::Marshal;
+
+    const SSRC: u32 = 0x1234_5678;
+
+    /// Scattered losses, one FCI covers two of them.
+    const SPARSE: [u16; 3] = [100, 102, 140];
+
+    fn burst() -> Vec<u16> {
+        (200..240).collect()
+    }
+
+    fn bitmask(fcis: &[Fci]) -> Nack {
+        Nack::Bitmask(GenericNack::new(SSRC, fcis.to_vec()))
+    }
+
+    fn range(ranges: &[PacketRangeRequest]) -> Nack {
+        Nack::Range(RangeBasedNACK::new(SSRC, ranges.to_vec()))
+    }
+
+    #[test]
+    fn smallest_encoding_is_chosen() {
+        let encoder = NackEncoder::new(NackFormats::Any, 1500);
+        assert_eq!(
+            encoder.encode(SSRC, &SPARSE),
+            [bitmask(&[
+                Fci {
+                    pid: 100,
+                    blp: 0x0002
+                },
+                Fci { pid: 140, blp: 0 }
+            ])]
+        );
+        assert_eq!(
+            encoder.encode(SSRC, &burst()),
+            [range(&[PacketRangeRequest {
+                seq_start: 200,
+                nb_consecutive: 39
+            }])]
+        );
+        // Same size, the Generic NACK wins.
+        assert_eq!(
+            encoder.encode(SSRC, &[10, 11]),
+            [bitmask(&[Fci { pid: 10, blp: 1 }])]
+        );
+        assert_eq!(encoder.encode(SSRC, &[]), []);
+    }
+
+    #[test]
+    fn formats_of_the_peer_are_honored() {
+        let bitmask_only = NackEncoder::new(NackFormats::Bitmask, 1500);
+        assert!(matches!(
+            &bitmask_only.encode(SSRC, &burst())[..],
+            [Nack::Bitmask(nack)] if nack.fcis.len() == 3
+        ));
+
+        let range_only = NackEncoder::new(NackFormats::Range, 1500);
+        assert!(matches!(
+            &range_only.encode(SSRC, &SPARSE)[..],
+            [Nack::Range(nack)] if nack.packet_ranges.len() == 3
+        ));
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let lost = [65534, 65535, 0, 1];
+        assert_eq!(
+            NackEncoder::new(NackFormats::Bitmask, 1500).encode(SSRC, &lost),
+            [bitmask(&[Fci {
+                pid: 65534,
+                blp: 0x0007
+            }])]
+        );
+        assert_eq!(
+            NackEncoder::new(NackFormats::Range, 1500).encode(SSRC, &lost),
+            [range(&[PacketRangeRequest {
+                seq_start: 65534,
+                nb_consecutive: 3
+            }])]
+        );
+    }
+
+    #[test]
+    fn packets_are_split_at_max_packet_size() {
+        // Room for two entries per packet.
+        let max_packet_size = NACK_HEADER_SIZE + 2 * ENTRY_SIZE;
+        let lost = [0, 20, 40, 60, 80];
+
+        for formats in [NackFormats::Bitmask, NackFormats::Range] {
+            let nacks = NackEncoder::new(formats, max_packet_size).encode(SSRC, &lost);
+            assert_eq!(nacks.len(), 3);
+            for nack in &nacks {
+                let size = match nack {
+                    Nack::Bitmask(nack) => nack.marshal_size(),
+                    Nack::Range(nack) => nack.marshal_size(),
+                };
+                assert!(size <= max_packet_size);
+            }
+
+            let requested: Vec<u16> = nacks
+                .iter()
+                .flat_map(|nack| match nack {
+                    Nack::Bitmask(nack) => nack
+                        .fcis
+                        .iter()
+                        .flat_map(Fci::sequence_numbers)
+                        .collect::<Vec<_>>(),
+                    Nack::Range(nack) => nack
+                        .packet_ranges
+                        .iter()
+                        .flat_map(PacketRangeRequest::sequence_numbers)
+                        .collect(),
+                })
+                .collect();
+            assert_eq!(requested, lost);
+        }
+    }
+}
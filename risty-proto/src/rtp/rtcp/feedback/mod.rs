@@ -1,10 +1,24 @@
-mod nack;
-mod rist;
+pub mod encoder;
+pub mod nack;
+pub mod rist;
 
 /// This field identifies the type of the feedback message
-enum Subtype {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Subtype {
     RangeBasedNACK = 0,
     GenericNack = 1,
     EchoRequest = 2,
     EchoResponse = 3,
 }
+
+impl Subtype {
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Subtype::RangeBasedNACK),
+            1 => Some(Subtype::GenericNack),
+            2 => Some(Subtype::EchoRequest),
+            3 => Some(Subtype::EchoResponse),
+            _ => None,
+        }
+    }
+}
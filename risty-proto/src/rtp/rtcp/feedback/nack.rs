@@ -1,7 +1,12 @@
+use std::borrow::Cow;
+
 use super::Subtype;
 use crate::rtp::rtcp::header::{Header, PacketType, VERSION};
+use packed_struct::prelude::*;
+use risty_core::{Marshal, MarshalError, ParseError, Unmarshal};
 
 /// Bitmask-based retransmissions shall be requested using the Generic NACK Message.
+#[derive(Debug, Clone, PartialEq)]
 pub struct GenericNack<'a> {
     pub header: Header,
 
@@ -15,28 +20,79 @@ pub struct GenericNack<'a> {
     pub ssrc_media_src: u32,
 
     /// A Generic NACK message may contain multiple FCI fields.
-    pub fcis: &'a [Fci],
+    pub fcis: Cow<'a, [Fci]>,
 }
 
 impl<'a> GenericNack<'a> {
-    pub fn new(ssrc_media_src: u32, fcis: &'a [Fci]) -> Self {
+    pub fn new(ssrc_media_src: u32, fcis: impl Into<Cow<'a, [Fci]>>) -> Self {
+        let fcis = fcis.into();
         Self {
             header: Header {
                 version: VERSION.into(),
                 padding: false,
                 packet_specific: (Subtype::GenericNack as u8).into(),
                 packet_type: PacketType::Feedback as u8,
-                length: 0, //TODO
+                length: Self::calculate_length(fcis.len()),
             },
             ssrc_packet_sender: 0,
             ssrc_media_src,
             fcis,
         }
     }
+
+    /// Header, packet sender SSRC and media source SSRC, then one 32-bit word per FCI, minus one.
+    fn calculate_length(nb_fcis: usize) -> u16 {
+        2 + nb_fcis as u16
+    }
+}
+
+impl Marshal for GenericNack<'_> {
+    fn marshal(&self, buf: &mut [u8]) -> Result<usize, MarshalError> {
+        self.header.pack_to_slice(&mut buf[0..4])?;
+        buf[4..8].copy_from_slice(&self.ssrc_packet_sender.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.ssrc_media_src.to_be_bytes());
+
+        for (fci, chunk) in self.fcis.iter().zip(buf[12..].chunks_exact_mut(4)) {
+            chunk[0..2].copy_from_slice(&fci.pid.to_be_bytes());
+            chunk[2..4].copy_from_slice(&fci.blp.to_be_bytes());
+        }
+
+        Ok(self.marshal_size())
+    }
+
+    fn marshal_size(&self) -> usize {
+        12 + 4 * self.fcis.len()
+    }
+}
+
+impl Unmarshal<'_> for GenericNack<'static> {
+    fn unmarshal(buf: &[u8]) -> Result<Self, ParseError> {
+        let header = Header::unmarshal_as(buf, PacketType::Feedback)?;
+        header.expect_subtype(Subtype::GenericNack as u8)?;
+        if header.length < 2 {
+            return Err(ParseError::BadLength(header.length));
+        }
+
+        let fcis = buf[12..header.packet_size()]
+            .chunks_exact(4)
+            .map(|fci| Fci {
+                pid: u16::from_be_bytes([fci[0], fci[1]]),
+                blp: u16::from_be_bytes([fci[2], fci[3]]),
+            })
+            .collect();
+
+        Ok(Self {
+            ssrc_packet_sender: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+            ssrc_media_src: u32::from_be_bytes(buf[8..12].try_into().unwrap()),
+            fcis: Cow::Owned(fcis),
+            header,
+        })
+    }
 }
 
 /// Feedback Control Information (FCI): This field contains one or more instances of the
 /// 32-bit Generic NACK message. Each FCI can request up to 17 lost packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Fci {
     /// The PID field is used to specify a lost packet. The PID field refers to the RTP sequence number of the lost packet.
     pub pid: u16,
@@ -52,3 +108,74 @@ pub struct Fci {
     /// all the sender knows is that the receiver has not reported them as lost at this time.
     pub blp: u16,
 }
+
+impl Fci {
+    /// Sequence numbers of the packets reported lost by this FCI: the PID, then the ones flagged in the BLP.
+    pub fn sequence_numbers(&self) -> impl Iterator<Item = u16> + '_ {
+        std::iter::once(self.pid).chain(
+            (0..16)
+                .filter(|bit| self.blp & (1 << bit) != 0)
+                .map(|bit| self.pid.wrapping_add(bit + 1)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Generic NACK of TR-06-1: V=2, P=0, FMT=1, PT=205, then the packet sender SSRC, the media
+    /// source SSRC and two FCIs.
+    const GENERIC_NACK: [u8; 20] = [
+        0x81, 0xCD, 0x00, 0x04, // header, length 4
+        0x00, 0x00, 0x00, 0x00, // SSRC of packet sender, ignored by the RIST sender
+        0x12, 0x34, 0x56, 0x78, // SSRC of media source
+        0x00, 0x64, 0x00, 0x05, // PID 100, BLP: 101 and 103 lost
+        0xFF, 0xFF, 0x80, 0x00, // PID 65535, BLP: 15 (after wrapping) lost
+    ];
+
+    fn fcis() -> [Fci; 2] {
+        [
+            Fci {
+                pid: 100,
+                blp: 0x0005,
+            },
+            Fci {
+                pid: 0xFFFF,
+                blp: 0x8000,
+            },
+        ]
+    }
+
+    #[test]
+    fn marshal_matches_spec() {
+        let fcis = fcis();
+        let nack = GenericNack::new(0x1234_5678, &fcis[..]);
+        let mut buf = vec![0; nack.marshal_size()];
+        assert_eq!(nack.marshal(&mut buf).unwrap(), GENERIC_NACK.len());
+        assert_eq!(buf, GENERIC_NACK);
+    }
+
+    #[test]
+    fn unmarshal_matches_spec() {
+        let fcis = fcis();
+        let nack = GenericNack::unmarshal(&GENERIC_NACK).unwrap();
+        assert_eq!(nack, GenericNack::new(0x1234_5678, &fcis[..]));
+        assert_eq!(
+            nack.fcis
+                .iter()
+                .flat_map(Fci::sequence_numbers)
+                .collect::<Vec<_>>(),
+            [100, 101, 103, 0xFFFF, 15]
+        );
+    }
+
+    #[test]
+    fn unmarshal_rejects_range_based_nack() {
+        // Same layout carried in an APP packet (PT=204) with subtype 0.
+        let mut buf = GENERIC_NACK;
+        buf[0] = 0x80;
+        buf[1] = 0xCC;
+        assert!(GenericNack::unmarshal(&buf).is_err());
+    }
+}
@@ -1,52 +1,55 @@
-use std::time::Instant;
+use std::borrow::Cow;
 
 use super::Subtype;
 use crate::rtp::rtcp::header::{Header, PacketType, VERSION};
 
 use packed_struct::prelude::*;
-use risty_core::Marshal;
+use risty_core::{Marshal, NtpTime, ParseError, Unmarshal};
 
-const RIST_NAME: u32 = 0x52495354;
+pub(crate) const RIST_NAME: u32 = 0x52495354;
 
 /// The purpose of the RTCP RTT Echo Request/Response packets is to allow RIST endpoints to measure
 /// the Round Trip Time (RTT) to the remote endpoint. The RTT information can be used by receivers
 /// to optimize their retransmission requests
+#[derive(Debug, Clone, PartialEq)]
 pub struct RttEcho {
-    header: Header,
+    pub header: Header,
 
     /// The synchronization source identifier of the media source that this feedback request is related to.
     /// The LSB of the SSRC is used to differentiate between original packets and retransmitted packets.
     /// The RIST receiver may use either value in the request packet.
     /// * SSRC LSB=0: Original Packet
     /// * SSRC LSB=1: Retransmission Packet
-    ssrc: u32,
+    pub ssrc: u32,
 
     /// This field identifies the application
-    name: u32,
+    pub name: u32,
 
     /// The originator of this message (Subtype = 2) shall fill in an arbitrary value in this field,
     /// and the recipient of the message shall echo it back in the response (Subtype = 3). In order
     /// to aid debugging, the timestamp may be in NTP format: the Timestamp most significant word may
     /// be a value in seconds, and the Timestamp least significant word may be the fractional part.
     /// There is no requirement that this be the actual NTP time or that the nodes be NTP synchronized.
-    timestamp: u64,
+    pub timestamp: u64,
 
     /// The processing time is defined as the interval between the instant the RTT Echo Request message
-    /// is received and the RTT Echo Response message is transmitted.
-    processing_delay: u64,
+    /// is received and the RTT Echo Response message is transmitted. It is expressed in the same 64-bit
+    /// NTP format as the timestamp.
+    pub processing_delay: u64,
 
     /// The RTT Echo Request sender may want to measure the RTT for a packet of a certain size, so it may
     /// pad the packet with a number of additional bytes, with arbitrary content. The only constraints are
     /// that the number of padding bytes shall be a multiple of 4, and the resulting compound RTCP packet
     /// shall not exceed the link MTU.
     /// This field corresponds to the number of 32 bits padding.
-    padding_size: u32,
+    pub padding_size: u32,
 }
 
 impl RttEcho {
     /// Creates a new RTT echo request.
     /// - `padding_size` is the number of 32 bits padding you want.
-    pub fn new_request(ssrc: u32, padding_size: u32, now: Instant) -> Self {
+    /// - `now` is the time the request is sent at, echoed back by the peer to compute the RTT.
+    pub fn new_request(ssrc: u32, padding_size: u32, now: NtpTime) -> Self {
         Self {
             header: Header {
                 version: VERSION.into(),
@@ -57,7 +60,7 @@ impl RttEcho {
             },
             ssrc,
             name: RIST_NAME,
-            timestamp: 0x55555555AAAAAAAA, // The originator of this message (Subtype = 2) shall fill in an arbitrary value in this field
+            timestamp: now.into(),
             processing_delay: 0, // In RTT Echo Request messages (Subtype = 2), the message sender shall fill this field with zeros
             padding_size,
         }
@@ -86,7 +89,7 @@ impl RttEcho {
     }
 
     fn calculate_length(padding_size: u32) -> u16 {
-        5 + padding_size as u16
+        6 + padding_size as u16
     }
 }
 
@@ -106,8 +109,35 @@ impl Marshal for RttEcho {
     }
 }
 
+impl Unmarshal<'_> for RttEcho {
+    fn unmarshal(buf: &[u8]) -> Result<Self, ParseError> {
+        let header = Header::unmarshal_as(buf, PacketType::App)?;
+        let subtype = u8::from(header.packet_specific);
+        if subtype != Subtype::EchoRequest as u8 && subtype != Subtype::EchoResponse as u8 {
+            return Err(ParseError::UnexpectedSubtype(subtype));
+        }
+        if header.length < Self::calculate_length(0) {
+            return Err(ParseError::BadLength(header.length));
+        }
+        let name = u32::from_be_bytes(buf[8..12].try_into().unwrap());
+        if name != RIST_NAME {
+            return Err(ParseError::UnexpectedName(name));
+        }
+
+        Ok(Self {
+            ssrc: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+            name,
+            timestamp: u64::from_be_bytes(buf[12..20].try_into().unwrap()),
+            processing_delay: u64::from_be_bytes(buf[20..28].try_into().unwrap()),
+            padding_size: (header.length - Self::calculate_length(0)) as u32,
+            header,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct RangeBasedNACK<'a> {
-    header: Header,
+    pub header: Header,
 
     /// The synchronization source identifier of the media source that this feedback request is related to.
     /// The LSB of the SSRC is used to differentiate between original packets and retransmitted packets.
@@ -119,35 +149,184 @@ pub struct RangeBasedNACK<'a> {
     /// This field identifies the applications
     pub name: u32,
 
-    pub packet_ranges: &'a [PacketRangeRequest],
+    pub packet_ranges: Cow<'a, [PacketRangeRequest]>,
 }
 
 impl<'a> RangeBasedNACK<'a> {
-    pub fn new(ssrc: u32, packet_ranges: &'a [PacketRangeRequest]) -> Self {
+    pub fn new(ssrc: u32, packet_ranges: impl Into<Cow<'a, [PacketRangeRequest]>>) -> Self {
+        let packet_ranges = packet_ranges.into();
         Self {
             header: Header {
                 version: VERSION.into(),
                 padding: false,
                 packet_specific: (Subtype::RangeBasedNACK as u8).into(),
-                packet_type: PacketType::Feedback as u8,
-                length: 0, //TODO
+                packet_type: PacketType::App as u8,
+                length: Self::calculate_length(packet_ranges.len()),
             },
             ssrc,
             name: RIST_NAME,
             packet_ranges,
         }
     }
+
+    /// Header, SSRC and name, then one 32-bit word per range, minus one.
+    fn calculate_length(nb_ranges: usize) -> u16 {
+        2 + nb_ranges as u16
+    }
+}
+
+impl Marshal for RangeBasedNACK<'_> {
+    fn marshal(&self, buf: &mut [u8]) -> Result<usize, risty_core::MarshalError> {
+        self.header.pack_to_slice(&mut buf[0..4])?;
+        buf[4..8].copy_from_slice(&self.ssrc.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.name.to_be_bytes());
+
+        for (range, chunk) in self.packet_ranges.iter().zip(buf[12..].chunks_exact_mut(4)) {
+            chunk[0..2].copy_from_slice(&range.seq_start.to_be_bytes());
+            chunk[2..4].copy_from_slice(&range.nb_consecutive.to_be_bytes());
+        }
+
+        Ok(self.marshal_size())
+    }
+
+    fn marshal_size(&self) -> usize {
+        12 + 4 * self.packet_ranges.len()
+    }
+}
+
+impl Unmarshal<'_> for RangeBasedNACK<'static> {
+    fn unmarshal(buf: &[u8]) -> Result<Self, ParseError> {
+        let header = Header::unmarshal_as(buf, PacketType::App)?;
+        header.expect_subtype(Subtype::RangeBasedNACK as u8)?;
+        if header.length < 2 {
+            return Err(ParseError::BadLength(header.length));
+        }
+
+        let packet_ranges = buf[12..header.packet_size()]
+            .chunks_exact(4)
+            .map(|range| PacketRangeRequest {
+                seq_start: u16::from_be_bytes([range[0], range[1]]),
+                nb_consecutive: u16::from_be_bytes([range[2], range[3]]),
+            })
+            .collect();
+
+        Ok(Self {
+            ssrc: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+            name: u32::from_be_bytes(buf[8..12].try_into().unwrap()),
+            packet_ranges: Cow::Owned(packet_ranges),
+            header,
+        })
+    }
 }
 
 /// Packet Range Requests: these are 32- bit fields, each requesting one packet range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct PacketRangeRequest {
     /// RTP sequence number of the first packet dropped in the block
-    seq_start: u16,
+    pub seq_start: u16,
 
     /// Number consecutive packets being requested after the packet identified by the
     /// missing packet sequence start. For example, the Missing Packet Sequence Start is
     /// N and the Number of Additional Missing Packets is A, this indicates that packets
     /// from N to N+A inclusive have been lost. If A is zero, then only one packet (with
     /// sequence number N) is being requested.
-    nb_consecutive: u16,
+    pub nb_consecutive: u16,
+}
+
+impl PacketRangeRequest {
+    /// Sequence numbers of the packets requested by this range, from N to N+A inclusive.
+    pub fn sequence_numbers(&self) -> impl Iterator<Item = u16> {
+        let seq_start = self.seq_start;
+        (0..=self.nb_consecutive).map(move |offset| seq_start.wrapping_add(offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Range based NACK of TR-06-1: an APP packet (PT=204) with subtype 0 and the name "RIST",
+    /// then one packet range request per 32-bit word.
+    const RANGE_BASED_NACK: [u8; 20] = [
+        0x80, 0xCC, 0x00, 0x04, // header, length 4
+        0x12, 0x34, 0x56, 0x79, // SSRC of media source, LSB=1
+        0x52, 0x49, 0x53, 0x54, // "RIST"
+        0x00, 0x64, 0x00, 0x02, // 100 to 102
+        0xFF, 0xFF, 0x00, 0x00, // 65535 only
+    ];
+
+    /// RTT echo request of TR-06-1: subtype 2 with a timestamp and a zero processing delay.
+    const ECHO_REQUEST: [u8; 28] = [
+        0x82, 0xCC, 0x00, 0x06, // header, length 6
+        0x12, 0x34, 0x56, 0x78, // SSRC
+        0x52, 0x49, 0x53, 0x54, // "RIST"
+        0x00, 0x00, 0x00, 0x0A, 0x80, 0x00, 0x00, 0x00, // timestamp, 10.5 s in NTP format
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // processing delay
+    ];
+
+    fn ranges() -> [PacketRangeRequest; 2] {
+        [
+            PacketRangeRequest {
+                seq_start: 100,
+                nb_consecutive: 2,
+            },
+            PacketRangeRequest {
+                seq_start: 0xFFFF,
+                nb_consecutive: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn range_based_nack_marshal_matches_spec() {
+        let ranges = ranges();
+        let nack = RangeBasedNACK::new(0x1234_5679, &ranges[..]);
+        let mut buf = vec![0; nack.marshal_size()];
+        assert_eq!(nack.marshal(&mut buf).unwrap(), RANGE_BASED_NACK.len());
+        assert_eq!(buf, RANGE_BASED_NACK);
+    }
+
+    #[test]
+    fn range_based_nack_unmarshal_matches_spec() {
+        let ranges = ranges();
+        let nack = RangeBasedNACK::unmarshal(&RANGE_BASED_NACK).unwrap();
+        assert_eq!(nack, RangeBasedNACK::new(0x1234_5679, &ranges[..]));
+        assert_eq!(
+            nack.packet_ranges
+                .iter()
+                .flat_map(PacketRangeRequest::sequence_numbers)
+                .collect::<Vec<_>>(),
+            [100, 101, 102, 0xFFFF]
+        );
+    }
+
+    #[test]
+    fn range_based_nack_is_not_a_feedback_packet() {
+        // The same layout with PT=205 is a transport layer feedback packet, not a RIST one.
+        let mut buf = RANGE_BASED_NACK;
+        buf[1] = 0xCD;
+        assert!(RangeBasedNACK::unmarshal(&buf).is_err());
+    }
+
+    #[test]
+    fn echo_request_round_trip() {
+        let echo = RttEcho::unmarshal(&ECHO_REQUEST).unwrap();
+        assert_eq!(u8::from(echo.header.packet_specific), 2);
+        assert_eq!(echo.timestamp, 0x0000_000A_8000_0000);
+        assert_eq!(echo.padding_size, 0);
+
+        let mut buf = vec![0; echo.marshal_size()];
+        echo.marshal(&mut buf).unwrap();
+        assert_eq!(buf, ECHO_REQUEST);
+    }
+
+    #[test]
+    fn echo_of_another_application_is_rejected() {
+        let mut buf = ECHO_REQUEST;
+        buf[8..12].copy_from_slice(b"ABCD");
+        assert!(matches!(
+            RttEcho::unmarshal(&buf),
+            Err(ParseError::UnexpectedName(0x4142_4344))
+        ));
+    }
 }
@@ -1,7 +1,12 @@
 use packed_struct::prelude::*;
+use risty_core::{ParseError, Unmarshal};
 
 pub(crate) const VERSION: u8 = 2;
 
+/// Size in bytes of the common RTCP header.
+pub(crate) const HEADER_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PacketType {
     SenderReport = 200,
     ReceiverReport = 201,
@@ -10,7 +15,22 @@ pub enum PacketType {
     Feedback = 205,
 }
 
-#[derive(PackedStruct, Debug)]
+impl TryFrom<u8> for PacketType {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            200 => Ok(PacketType::SenderReport),
+            201 => Ok(PacketType::ReceiverReport),
+            202 => Ok(PacketType::Sdes),
+            204 => Ok(PacketType::App),
+            205 => Ok(PacketType::Feedback),
+            other => Err(ParseError::UnknownPacketType(other)),
+        }
+    }
+}
+
+#[derive(PackedStruct, Debug, Clone, PartialEq)]
 #[packed_struct(bit_numbering = "msb0")]
 pub struct Header {
     /// Identifies the version of RTP, which is the same in RTCP packets as in RTP data packets.
@@ -40,3 +60,45 @@ pub struct Header {
     #[packed_field(bits = "16..=31", endian = "msb")]
     pub length: u16, //16
 }
+
+impl Header {
+    /// Size in bytes of the whole RTCP packet described by this header, as given by the length field.
+    pub fn packet_size(&self) -> usize {
+        (self.length as usize + 1) * 4
+    }
+}
+
+impl Unmarshal<'_> for Header {
+    /// Parses the common RTCP header and checks that the buffer holds the whole packet it describes.
+    fn unmarshal(buf: &[u8]) -> Result<Self, ParseError> {
+        ParseError::check_len(buf, HEADER_SIZE)?;
+        let header = Header::unpack_from_slice(&buf[..HEADER_SIZE])?;
+
+        if u8::from(header.version) != VERSION {
+            return Err(ParseError::BadVersion(header.version.into()));
+        }
+        ParseError::check_len(buf, header.packet_size())?;
+
+        Ok(header)
+    }
+}
+
+impl Header {
+    /// Parses the common RTCP header and checks that it announces the expected packet type.
+    pub(crate) fn unmarshal_as(buf: &[u8], packet_type: PacketType) -> Result<Self, ParseError> {
+        let header = Header::unmarshal(buf)?;
+        if header.packet_type != packet_type as u8 {
+            return Err(ParseError::UnexpectedPacketType(header.packet_type));
+        }
+        Ok(header)
+    }
+
+    /// Checks the subtype carried in the packet specific bits of APP and feedback packets.
+    pub(crate) fn expect_subtype(&self, subtype: u8) -> Result<(), ParseError> {
+        let actual = u8::from(self.packet_specific);
+        if actual != subtype {
+            return Err(ParseError::UnexpectedSubtype(actual));
+        }
+        Ok(())
+    }
+}
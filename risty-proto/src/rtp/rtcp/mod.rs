@@ -1,6 +1,8 @@
-mod feedback;
-mod header;
-mod receiver_report;
-mod report_block;
-mod sdes;
-mod sender_report;
+pub mod compound;
+pub mod feedback;
+pub mod header;
+pub mod packet;
+pub mod receiver_report;
+pub mod report_block;
+pub mod sdes;
+pub mod sender_report;
@@ -0,0 +1,108 @@
+use super::feedback::nack::GenericNack;
+use super::feedback::rist::{RangeBasedNACK, RttEcho, RIST_NAME};
+use super::feedback::Subtype;
+use super::header::{Header, PacketType};
+use super::receiver_report::ReceiverReport;
+use super::sdes::Sdes;
+use super::sender_report::SenderReport;
+use risty_core::{Marshal, MarshalError, ParseError, Unmarshal};
+
+/// Any RTCP packet a RIST endpoint may receive. Packets that RIST does not define are kept as raw bytes
+/// in the `Unknown` variant, so they can be logged or forwarded untouched.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RtcpPacket<'a> {
+    SenderReport(SenderReport),
+    ReceiverReport(ReceiverReport),
+    Sdes(Sdes),
+    RttEchoRequest(RttEcho),
+    RttEchoResponse(RttEcho),
+    GenericNack(GenericNack<'a>),
+    RangeBasedNACK(RangeBasedNACK<'a>),
+    Unknown { header: Header, bytes: &'a [u8] },
+}
+
+impl RtcpPacket<'_> {
+    pub fn header(&self) -> &Header {
+        match self {
+            RtcpPacket::SenderReport(sr) => &sr.header,
+            RtcpPacket::ReceiverReport(rr) => &rr.header,
+            RtcpPacket::Sdes(sdes) => &sdes.header,
+            RtcpPacket::RttEchoRequest(echo) | RtcpPacket::RttEchoResponse(echo) => &echo.header,
+            RtcpPacket::GenericNack(nack) => &nack.header,
+            RtcpPacket::RangeBasedNACK(nack) => &nack.header,
+            RtcpPacket::Unknown { header, .. } => header,
+        }
+    }
+}
+
+impl<'a> Unmarshal<'a> for RtcpPacket<'a> {
+    /// Parses the first RTCP packet of `buf`, dispatching on the packet type and, for APP and feedback
+    /// packets, on the subtype carried in the packet specific bits.
+    fn unmarshal(buf: &'a [u8]) -> Result<Self, ParseError> {
+        let header = Header::unmarshal(buf)?;
+        let buf = &buf[..header.packet_size()];
+        let subtype = Subtype::from_u8(header.packet_specific.into());
+
+        let packet = match PacketType::try_from(header.packet_type) {
+            Ok(PacketType::SenderReport) => RtcpPacket::SenderReport(SenderReport::unmarshal(buf)?),
+            Ok(PacketType::ReceiverReport) => {
+                RtcpPacket::ReceiverReport(ReceiverReport::unmarshal(buf)?)
+            }
+            Ok(PacketType::Sdes) => RtcpPacket::Sdes(Sdes::unmarshal(buf)?),
+            Ok(PacketType::App) if is_rist_app(buf) => match subtype {
+                Some(Subtype::RangeBasedNACK) => {
+                    RtcpPacket::RangeBasedNACK(RangeBasedNACK::unmarshal(buf)?)
+                }
+                Some(Subtype::EchoRequest) => RtcpPacket::RttEchoRequest(RttEcho::unmarshal(buf)?),
+                Some(Subtype::EchoResponse) => {
+                    RtcpPacket::RttEchoResponse(RttEcho::unmarshal(buf)?)
+                }
+                _ => RtcpPacket::Unknown { header, bytes: buf },
+            },
+            Ok(PacketType::Feedback) if subtype == Some(Subtype::GenericNack) => {
+                RtcpPacket::GenericNack(GenericNack::unmarshal(buf)?)
+            }
+            _ => RtcpPacket::Unknown { header, bytes: buf },
+        };
+
+        Ok(packet)
+    }
+}
+
+/// APP packets carry a name identifying the application, only the ones named "RIST" are understood.
+fn is_rist_app(buf: &[u8]) -> bool {
+    buf.len() >= 12 && u32::from_be_bytes(buf[8..12].try_into().unwrap()) == RIST_NAME
+}
+
+impl Marshal for RtcpPacket<'_> {
+    fn marshal(&self, buf: &mut [u8]) -> Result<usize, MarshalError> {
+        match self {
+            RtcpPacket::SenderReport(sr) => sr.marshal(buf),
+            RtcpPacket::ReceiverReport(rr) => rr.marshal(buf),
+            RtcpPacket::Sdes(sdes) => sdes.marshal(buf),
+            RtcpPacket::RttEchoRequest(echo) | RtcpPacket::RttEchoResponse(echo) => {
+                echo.marshal(buf)
+            }
+            RtcpPacket::GenericNack(nack) => nack.marshal(buf),
+            RtcpPacket::RangeBasedNACK(nack) => nack.marshal(buf),
+            RtcpPacket::Unknown { bytes, .. } => {
+                buf[..bytes.len()].copy_from_slice(bytes);
+                Ok(bytes.len())
+            }
+        }
+    }
+
+    fn marshal_size(&self) -> usize {
+        match self {
+            RtcpPacket::SenderReport(sr) => sr.marshal_size(),
+            RtcpPacket::ReceiverReport(rr) => rr.marshal_size(),
+            RtcpPacket::Sdes(sdes) => sdes.marshal_size(),
+            RtcpPacket::RttEchoRequest(echo) | RtcpPacket::RttEchoResponse(echo) => {
+                echo.marshal_size()
+            }
+            RtcpPacket::GenericNack(nack) => nack.marshal_size(),
+            RtcpPacket::RangeBasedNACK(nack) => nack.marshal_size(),
+            RtcpPacket::Unknown { bytes, .. } => bytes.len(),
+        }
+    }
+}
@@ -1,15 +1,17 @@
 use super::{header::Header, header::PacketType, header::VERSION, report_block::ReportBlock};
 use packed_struct::prelude::*;
-use risty_core::{Marshal, MarshalError};
+use risty_core::{Marshal, MarshalError, ParseError, Unmarshal};
 
 const EMPTY_RR_LENGTH: u16 = 1;
 const RR_LENGTH: u16 = 7;
+const REPORT_BLOCK_SIZE: usize = 24;
 
 /// Receiver report, for reception statistics from participants that are not active senders and
 /// in combination with SR for active senders reporting on more than 31 sources
 /// * Note: Typically RR can include more than 1 report block, but in RIST this is fixed to
 ///     0 for empty RR or 1 for RR.
-struct ReceiverReport {
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiverReport {
     pub header: Header,
 
     /// The synchronization source identifier for the originator of this SR packet.
@@ -65,3 +67,25 @@ impl Marshal for ReceiverReport {
         8 + 24 * self.report_block.len()
     }
 }
+
+impl Unmarshal<'_> for ReceiverReport {
+    fn unmarshal(buf: &[u8]) -> Result<Self, ParseError> {
+        let header = Header::unmarshal_as(buf, PacketType::ReceiverReport)?;
+        let count = u8::from(header.packet_specific) as usize;
+        if header.packet_size() < 8 + REPORT_BLOCK_SIZE * count {
+            return Err(ParseError::BadLength(header.length));
+        }
+
+        let ssrc_sender = u32::from_be_bytes(buf[4..8].try_into().unwrap());
+        let report_block = buf[8..8 + REPORT_BLOCK_SIZE * count]
+            .chunks_exact(REPORT_BLOCK_SIZE)
+            .map(ReportBlock::unpack_from_slice)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            header,
+            ssrc_sender,
+            report_block,
+        })
+    }
+}
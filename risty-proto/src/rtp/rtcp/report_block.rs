@@ -1,6 +1,6 @@
 use packed_struct::prelude::*;
 
-#[derive(PackedStruct)]
+#[derive(PackedStruct, Debug, Clone, PartialEq)]
 #[packed_struct(bit_numbering = "msb0")]
 pub struct ReportBlock {
     /// The SSRC identifier of the source to which the information in this reception report block pertains.
@@ -1,6 +1,9 @@
 use super::header::{Header, PacketType, VERSION};
+use packed_struct::prelude::*;
+use risty_core::{Marshal, MarshalError, ParseError, Unmarshal};
 
-struct Sdes {
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sdes {
     pub header: Header,
 
     /// Chunk consists of an SSRC/CSRC identifier followed by a list of zero or more items,
@@ -31,15 +34,16 @@ fn sdes_header(length: u16) -> Header {
 /// The length of this RTCP packet in 32-bit words minus one, including the header and any padding
 fn calculate_sdes_length(name_length: u8) -> u16 {
     //Sizes in bytes
-    const HEADER_SIZE: u8 = 4;
-    const CHUNK_HEADER_SIZE: u8 = 6;
+    const HEADER_SIZE: u16 = 4;
+    const CHUNK_HEADER_SIZE: u16 = 6;
 
-    let size = HEADER_SIZE + CHUNK_HEADER_SIZE + name_length;
+    let size = HEADER_SIZE + CHUNK_HEADER_SIZE + name_length as u16;
 
     // 32-bit words minus one, so an integer division will include any padding here
-    (size / 4) as u16
+    size / 4
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Chunk {
     /// The synchronization source identifier for the originator of this SDES packet
     pub ssrc: u32,
@@ -66,3 +70,52 @@ impl Chunk {
         }
     }
 }
+
+impl Marshal for Sdes {
+    fn marshal(&self, buf: &mut [u8]) -> Result<usize, MarshalError> {
+        self.header.pack_to_slice(&mut buf[0..4])?;
+        buf[4..8].copy_from_slice(&self.chunk.ssrc.to_be_bytes());
+        buf[8] = self.chunk.cname;
+        buf[9] = self.chunk.name_length;
+
+        let name_end = 10 + self.chunk.name_length as usize;
+        buf[10..name_end].copy_from_slice(&self.chunk.user_and_domain.as_bytes()[..name_end - 10]);
+        // The item list is terminated by at least one null octet, then padded to a 32-bit boundary.
+        buf[name_end..self.marshal_size()].fill(0);
+
+        Ok(self.marshal_size())
+    }
+
+    fn marshal_size(&self) -> usize {
+        self.header.packet_size()
+    }
+}
+
+impl Unmarshal<'_> for Sdes {
+    fn unmarshal(buf: &[u8]) -> Result<Self, ParseError> {
+        let header = Header::unmarshal_as(buf, PacketType::Sdes)?;
+        // The chunk header (SSRC, item type and item length) must fit in the packet.
+        if u8::from(header.packet_specific) == 0 || header.packet_size() < 10 {
+            return Err(ParseError::BadLength(header.length));
+        }
+
+        let ssrc = u32::from_be_bytes(buf[4..8].try_into().unwrap());
+        let cname = buf[8];
+        let name_length = buf[9];
+        let name_end = 10 + name_length as usize;
+        if name_end > header.packet_size() {
+            return Err(ParseError::BadLength(header.length));
+        }
+        let user_and_domain = String::from_utf8_lossy(&buf[10..name_end]).into_owned();
+
+        Ok(Self {
+            header,
+            chunk: Chunk {
+                ssrc,
+                cname,
+                name_length,
+                user_and_domain,
+            },
+        })
+    }
+}
@@ -1,28 +1,41 @@
 use super::{header::Header, header::PacketType, header::VERSION};
 use packed_struct::prelude::*;
-use risty_core::Marshal;
+use risty_core::{Marshal, NtpTime, ParseError, Unmarshal};
 
 const SR_LENGTH: u16 = 6;
 
 /// Sender report, for transmission and reception statistics from participants that are active senders.
 /// * Note: Typically SenderReport can include 1 or many Report Blocks, but in RIST this is not used, so this
 ///     is ommited in the struct.
-#[derive(PackedStruct, Debug)]
+#[derive(PackedStruct, Debug, Clone, PartialEq)]
 #[packed_struct(bit_numbering = "msb0")]
 pub struct SenderReport {
     #[packed_field(bytes = "0..=3", endian = "msb")]
-    header: Header,
+    pub header: Header,
 
     /// The synchronization source identifier for the originator of this SR packet.
     #[packed_field(bytes = "4..=7", endian = "msb")]
-    ssrc_sender: u32,
+    pub ssrc_sender: u32,
 
     #[packed_field(bytes = "8..=27", endian = "msb")]
-    sender_info: SenderInfo,
+    pub sender_info: SenderInfo,
 }
 
 impl SenderReport {
     pub fn new(sender_ssrc: u32) -> Self {
+        Self::new_with_sender_info(
+            sender_ssrc,
+            SenderInfo {
+                ntp_ts: 0,
+                rtp_ts: 0,
+                sender_packet_count: 0,
+                sender_octet_count: 0,
+            },
+        )
+    }
+
+    /// Creates a sender report from a snapshot of the sender statistics.
+    pub fn new_with_sender_info(sender_ssrc: u32, sender_info: SenderInfo) -> Self {
         Self {
             header: Header {
                 version: VERSION.into(),
@@ -32,12 +45,7 @@ impl SenderReport {
                 length: SR_LENGTH,
             },
             ssrc_sender: sender_ssrc,
-            sender_info: SenderInfo {
-                ntp_ts: 0,
-                rtp_ts: 0,
-                sender_packet_count: 0,
-                sender_octet_count: 0,
-            },
+            sender_info,
         }
     }
 }
@@ -52,8 +60,20 @@ impl Marshal for SenderReport {
     }
 }
 
+impl Unmarshal<'_> for SenderReport {
+    fn unmarshal(buf: &[u8]) -> Result<Self, ParseError> {
+        let header = Header::unmarshal_as(buf, PacketType::SenderReport)?;
+        // Report blocks may follow the sender info, they are not used by RIST and are skipped.
+        if header.length < SR_LENGTH {
+            return Err(ParseError::BadLength(header.length));
+        }
+
+        Ok(Self::unpack_from_slice(&buf[0..=27])?)
+    }
+}
+
 /// It summarizes the data transmissions from this sender.
-#[derive(PackedStruct, Debug)]
+#[derive(PackedStruct, Debug, Clone, PartialEq)]
 #[packed_struct(bit_numbering = "msb0")]
 pub struct SenderInfo {
     /// Indicates the wallclock time when this report was sent. The most significant 32 bits on this field
@@ -84,3 +104,23 @@ pub struct SenderInfo {
     #[packed_field(bytes = "16..=19", endian = "msb")]
     pub sender_octet_count: u32,
 }
+
+impl SenderInfo {
+    pub fn new(
+        ntp_time: NtpTime,
+        rtp_ts: u32,
+        sender_packet_count: u32,
+        sender_octet_count: u32,
+    ) -> Self {
+        Self {
+            ntp_ts: ntp_time.into(),
+            rtp_ts,
+            sender_packet_count,
+            sender_octet_count,
+        }
+    }
+
+    pub fn ntp_time(&self) -> NtpTime {
+        self.ntp_ts.into()
+    }
+}
@@ -1,22 +1,43 @@
-use std::time::Duration;
+use std::net::SocketAddr;
 
 use num::Integer;
 
-struct Config {
-    buffer_size: Duration,
-    max_number_of_retry_per_packet: u32,
-}
+use crate::error::ConfigError;
+
+/// RTP timestamp clock rate of MPEG-2 TS payloads, in Hz.
+pub const MPEG_TS_CLOCK_RATE: u32 = 90_000;
+
+/// Largest UDP payload sent, so that datagrams fit in a 1500 bytes Ethernet MTU with the IPv4 and UDP headers.
+pub const MAX_DATAGRAM_SIZE: usize = 1472;
 
 /// RIST senders shall transmit the RTP media packets to the configured IP address of the RIST
 /// receiver and a user-selected UDP destination port P, where P is an even number between 2
 /// and 65534.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RistListenerPort(u16);
 impl RistListenerPort {
-    pub fn new(port: u16) -> Result<Self, String> {
-        if port.is_even() && (2..=65534).contains(&port) {
-            Ok(Self(port))
+    pub fn new(port: u16) -> Result<Self, ConfigError> {
+        if !(2..=65534).contains(&port) {
+            Err(ConfigError::PortOutOfRange(port))
+        } else if port.is_odd() {
+            Err(ConfigError::OddPort(port))
         } else {
-            Err("TODO make an error enum!".to_string())
+            Ok(Self(port))
         }
     }
+
+    pub fn get(&self) -> u16 {
+        self.0
+    }
+}
+
+/// A datagram produced by a state machine, to be sent by the I/O driver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transmit {
+    pub destination: SocketAddr,
+
+    /// Local UDP port the datagram shall be sent from, which selects the RTP or the RTCP socket.
+    pub source_port: u16,
+
+    pub bytes: Vec<u8>,
 }
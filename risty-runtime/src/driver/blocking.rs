@@ -0,0 +1,451 @@
+//! Blocking driver, running each endpoint on a dedicated thread with `std::net` sockets. Each socket is read by
+//! its own thread, which forwards the datagrams to the endpoint thread along with the payloads of the
+//! application. The endpoint thread blocks on them until its next deadline.
+
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::{
+    bind_receiver, bind_sender, bind_tunnel_receiver, bind_tunnel_sender, is_transient, Endpoint,
+    ReceiverEndpoint, SenderEndpoint, Socket, RECV_BUFFER_SIZE,
+};
+use crate::common::RistListenerPort;
+use crate::error::{BufferOverflow, RistError};
+use crate::receiver;
+use crate::rtp_sender::{check_payload_size, MAX_PAYLOAD_SIZE};
+use crate::sender::{Sender, SenderConfig};
+use crate::session::SessionEvent;
+use crate::tunnel;
+#[cfg(feature = "upnp")]
+use crate::upnp::{self, PortMapping};
+
+/// Number of payloads and datagrams queued for the endpoint thread.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// The reader threads block on their socket for this long before checking whether the driver stopped. It bounds
+/// the time dropping an endpoint takes.
+const READER_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// RIST sender sending its media from a dedicated thread. The thread stops when the sender is dropped, or on
+/// the first error, see [`BlockingSender::closed`].
+pub struct BlockingSender {
+    events: Receiver<SessionEvent>,
+    thread: DriverThread,
+
+    /// Largest payload accepted, smaller in tunnel mode to leave room for the GRE header.
+    max_payload_size: usize,
+}
+
+impl BlockingSender {
+    /// Binds the RTP socket on the RTP source port and the RTCP socket on the RTCP source port, then
+    /// starts sending to the receiver.
+    pub fn bind(config: SenderConfig) -> Result<Self, RistError> {
+        config.validate()?;
+        let (rtp, rtcp) = bind_sender(&config)?;
+        let endpoint = SenderEndpoint {
+            rtp_port: config.rtp_config.rtp_source_port,
+            sender: Sender::new(config, Instant::now())?,
+        };
+
+        Self::spawn(endpoint, rtp, Some(rtcp), MAX_PAYLOAD_SIZE)
+    }
+
+    /// Binds a single socket on the RTP source port, then starts sending to the receiver through a GRE tunnel
+    /// to its port P. The RTCP source port of the configuration is not used.
+    pub fn bind_tunnel(config: SenderConfig, tunnel: tunnel::Config) -> Result<Self, RistError> {
+        let (endpoint, socket) = bind_tunnel_sender(config, tunnel)?;
+        Self::spawn(endpoint, socket, None, tunnel::MAX_PAYLOAD_SIZE)
+    }
+
+    fn spawn<E: Endpoint + Send + 'static>(
+        endpoint: E,
+        rtp: UdpSocket,
+        rtcp: Option<UdpSocket>,
+        max_payload_size: usize,
+    ) -> Result<Self, RistError> {
+        let (events_tx, events) = mpsc::channel();
+        let thread = DriverThread::spawn(endpoint, rtp, rtcp, None, events_tx)?;
+        Ok(Self {
+            events,
+            thread,
+            max_payload_size,
+        })
+    }
+
+    /// Queues a media payload, typically 7 MPEG-2 TS packets of 188 bytes. Blocks while the queue is full.
+    pub fn send(&self, payload: &[u8]) -> Result<(), RistError> {
+        check_payload_size(payload, self.max_payload_size)?;
+        self.thread
+            .inputs
+            .send(Input::Payload(payload.to_vec(), Instant::now()))
+            .map_err(|_| RistError::Closed)
+    }
+
+    /// Like [`BlockingSender::send`], but fails instead of blocking when the queue is full.
+    pub fn try_send(&self, payload: &[u8]) -> Result<(), RistError> {
+        check_payload_size(payload, self.max_payload_size)?;
+        self.thread
+            .inputs
+            .try_send(Input::Payload(payload.to_vec(), Instant::now()))
+            .map_err(|err| match err {
+                TrySendError::Full(_) => RistError::BufferOverflow(BufferOverflow::QueueFull),
+                TrySendError::Disconnected(_) => RistError::Closed,
+            })
+    }
+
+    /// Next change of the session with the receiver, `None` if nothing changed since the last call.
+    pub fn try_next_event(&self) -> Result<Option<SessionEvent>, RistError> {
+        try_next_event(&self.events)
+    }
+
+    /// Whether the driver thread is still running.
+    pub fn is_running(&self) -> bool {
+        self.thread.is_running()
+    }
+
+    /// Blocks until the driver thread stops, and returns why: `RistError::PeerTimeout` when the receiver went
+    /// silent, or the I/O error that ended it.
+    pub fn closed(mut self) -> RistError {
+        self.thread.join()
+    }
+}
+
+/// RIST receiver receiving the media on a dedicated thread. The thread stops when the receiver is dropped, or
+/// on the first error, see [`BlockingReceiver::closed`].
+pub struct BlockingReceiver {
+    payloads: Receiver<Vec<u8>>,
+
+    /// Payloads dropped because the queue was full.
+    dropped: Arc<AtomicU64>,
+    events: Receiver<SessionEvent>,
+    thread: DriverThread,
+
+    #[cfg(feature = "upnp")]
+    listen_port: RistListenerPort,
+    #[cfg(feature = "upnp")]
+    port_mapping: Option<PortMapping>,
+}
+
+impl BlockingReceiver {
+    /// Binds the RTP socket on `address:P` and the RTCP socket on `address:P+1`, then starts receiving.
+    pub fn bind(
+        address: IpAddr,
+        listen_port: RistListenerPort,
+        config: receiver::Config,
+    ) -> Result<Self, RistError> {
+        config.validate()?;
+        let (rtp, rtcp) = bind_receiver(address, listen_port, &config)?;
+        let endpoint = ReceiverEndpoint {
+            receiver: receiver::Receiver::new(listen_port, config, Instant::now())?,
+            rtp_port: listen_port.get(),
+        };
+
+        Self::spawn(endpoint, rtp, Some(rtcp), listen_port)
+    }
+
+    /// Binds a single socket on `address:P`, then starts receiving through a GRE tunnel.
+    pub fn bind_tunnel(
+        address: IpAddr,
+        listen_port: RistListenerPort,
+        config: receiver::Config,
+        tunnel: tunnel::Config,
+    ) -> Result<Self, RistError> {
+        let (endpoint, socket) = bind_tunnel_receiver(address, listen_port, config, tunnel)?;
+        Self::spawn(endpoint, socket, None, listen_port)
+    }
+
+    fn spawn<E: Endpoint + Send + 'static>(
+        endpoint: E,
+        rtp: UdpSocket,
+        rtcp: Option<UdpSocket>,
+        #[cfg_attr(not(feature = "upnp"), allow(unused_variables))] listen_port: RistListenerPort,
+    ) -> Result<Self, RistError> {
+        let (payloads_tx, payloads) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (events_tx, events) = mpsc::channel();
+        let thread = DriverThread::spawn(
+            endpoint,
+            rtp,
+            rtcp,
+            Some((payloads_tx, dropped.clone())),
+            events_tx,
+        )?;
+
+        Ok(Self {
+            payloads,
+            dropped,
+            events,
+            thread,
+            #[cfg(feature = "upnp")]
+            listen_port,
+            #[cfg(feature = "upnp")]
+            port_mapping: None,
+        })
+    }
+
+    /// Asks the gateway of the local network to forward P and P+1 to this receiver. The mappings are renewed
+    /// until the receiver is dropped, then removed.
+    #[cfg(feature = "upnp")]
+    pub fn map_ports(&mut self, config: upnp::Config) -> Result<&PortMapping, RistError> {
+        let port_mapping = PortMapping::map(self.listen_port, config)?;
+        Ok(self.port_mapping.insert(port_mapping))
+    }
+
+    /// Next media payload, in sequence order, once the buffer latency elapsed. Payloads are dropped while the
+    /// queue is full, so that a slow application does not hold the protocol back.
+    pub fn recv(&self) -> Result<Vec<u8>, RistError> {
+        self.payloads.recv().map_err(|_| RistError::Closed)
+    }
+
+    /// Like [`BlockingReceiver::recv`], but gives up after `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<Vec<u8>>, RistError> {
+        match self.payloads.recv_timeout(timeout) {
+            Ok(payload) => Ok(Some(payload)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(RistError::Closed),
+        }
+    }
+
+    /// Number of payloads dropped because the application did not take them in time.
+    pub fn dropped_payloads(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Next change of the session with the sender, `None` if nothing changed since the last call.
+    pub fn try_next_event(&self) -> Result<Option<SessionEvent>, RistError> {
+        try_next_event(&self.events)
+    }
+
+    /// Whether the driver thread is still running.
+    pub fn is_running(&self) -> bool {
+        self.thread.is_running()
+    }
+
+    /// Blocks until the driver thread stops, and returns why: `RistError::PeerTimeout` when the sender went
+    /// silent, or the I/O error that ended it.
+    pub fn closed(mut self) -> RistError {
+        self.thread.join()
+    }
+}
+
+/// Input of the endpoint thread.
+enum Input {
+    Datagram {
+        socket: Socket,
+        bytes: Vec<u8>,
+        source: SocketAddr,
+    },
+
+    /// Payload of the application with the instant it was pushed at, which sets its RTP timestamp.
+    Payload(Vec<u8>, Instant),
+
+    /// A reader thread failed to read its socket.
+    Error(std::io::Error),
+
+    /// Wakes the endpoint thread up to stop.
+    Stop,
+}
+
+/// Thread running an endpoint and the threads reading its sockets, stopped and joined on drop.
+struct DriverThread {
+    inputs: SyncSender<Input>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Result<(), RistError>>>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl DriverThread {
+    /// - `rtcp` is `None` in tunnel mode, where every packet goes through the RTP socket.
+    /// - `payloads_out` counts the payloads dropped because the application did not take them in time.
+    fn spawn<E: Endpoint + Send + 'static>(
+        endpoint: E,
+        rtp: UdpSocket,
+        rtcp: Option<UdpSocket>,
+        payloads_out: Option<(SyncSender<Vec<u8>>, Arc<AtomicU64>)>,
+        events: mpsc::Sender<SessionEvent>,
+    ) -> Result<Self, RistError> {
+        let (inputs, inputs_rx) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let mut readers = vec![];
+        for (kind, socket) in [(Socket::Rtp, Some(&rtp)), (Socket::Rtcp, rtcp.as_ref())] {
+            let Some(socket) = socket else {
+                continue;
+            };
+            let socket = socket.try_clone()?;
+            socket.set_read_timeout(Some(READER_TIMEOUT))?;
+            let inputs = inputs.clone();
+            let stop = stop.clone();
+            readers.push(
+                thread::Builder::new()
+                    .name("risty-reader".to_string())
+                    .spawn(move || read(&socket, kind, &inputs, &stop))?,
+            );
+        }
+
+        let driver = Driver {
+            endpoint,
+            rtp,
+            rtcp,
+            inputs: inputs_rx,
+            payloads_out,
+            events,
+        };
+        let handle = thread::Builder::new()
+            .name("risty-driver".to_string())
+            .spawn(move || driver.run())?;
+
+        Ok(Self {
+            inputs,
+            stop,
+            handle: Some(handle),
+            readers,
+        })
+    }
+
+    /// Waits for the thread to stop. `RistError::Closed` if it stopped without error, or panicked.
+    fn join(&mut self) -> RistError {
+        match self.handle.take().map(JoinHandle::join) {
+            Some(Ok(Err(err))) => err,
+            _ => RistError::Closed,
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+}
+
+impl Drop for DriverThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Fails if the endpoint thread already stopped.
+        let _ = self.inputs.send(Input::Stop);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        // The sockets are closed once the readers are gone, their ports can be bound again.
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// Forwards the datagrams received on `socket` to the endpoint thread, until the driver stops.
+fn read(socket: &UdpSocket, kind: Socket, inputs: &SyncSender<Input>, stop: &AtomicBool) {
+    let mut buf = vec![0; RECV_BUFFER_SIZE];
+    while !stop.load(Ordering::Relaxed) {
+        let input = match socket.recv_from(&mut buf) {
+            Ok((len, source)) => Input::Datagram {
+                socket: kind,
+                bytes: buf[..len].to_vec(),
+                source,
+            },
+            Err(err) if is_transient(&err) => continue,
+            Err(err) => {
+                let _ = inputs.send(Input::Error(err));
+                return;
+            }
+        };
+        if inputs.send(input).is_err() {
+            return;
+        }
+    }
+}
+
+struct Driver<E> {
+    endpoint: E,
+    rtp: UdpSocket,
+    rtcp: Option<UdpSocket>,
+    inputs: Receiver<Input>,
+    payloads_out: Option<(SyncSender<Vec<u8>>, Arc<AtomicU64>)>,
+    events: mpsc::Sender<SessionEvent>,
+}
+
+impl<E: Endpoint> Driver<E> {
+    fn run(mut self) -> Result<(), RistError> {
+        loop {
+            let now = Instant::now();
+            while let Some(transmit) = self.endpoint.poll_transmit(now)? {
+                let socket = match &self.rtcp {
+                    Some(rtcp) if transmit.source_port != self.endpoint.rtp_port() => rtcp,
+                    _ => &self.rtp,
+                };
+                send_to(socket, &transmit.bytes, transmit.destination)?;
+            }
+            while let Some(event) = self.endpoint.poll_event() {
+                // Nobody listens to the events once the application dropped its end.
+                let _ = self.events.send(event);
+            }
+            // Stops once the application got the event closing the session.
+            if let Some(err) = self.endpoint.error() {
+                return Err(err);
+            }
+            if let Some((payloads_out, dropped)) = &self.payloads_out {
+                while let Some(payload) = self.endpoint.poll_payload(now) {
+                    // Waiting for the application would stall the reads, the NACKs and the reports.
+                    match payloads_out.try_send(payload) {
+                        Ok(()) => {}
+                        Err(TrySendError::Full(_)) => {
+                            dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(TrySendError::Disconnected(_)) => return Ok(()),
+                    }
+                }
+            }
+
+            let input = match self.endpoint.poll_timeout(now) {
+                Some(timeout) => match self
+                    .inputs
+                    .recv_timeout(timeout.saturating_duration_since(now))
+                {
+                    Ok(input) => input,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                },
+                None => match self.inputs.recv() {
+                    Ok(input) => input,
+                    Err(_) => return Ok(()),
+                },
+            };
+            match input {
+                Input::Datagram {
+                    socket,
+                    bytes,
+                    source,
+                } => {
+                    // Malformed datagrams are dropped, they shall not stop the flow.
+                    let _ = self
+                        .endpoint
+                        .handle_input(socket, &bytes, source, Instant::now());
+                }
+                Input::Payload(payload, pushed_at) => self.endpoint.push(&payload, pushed_at)?,
+                Input::Error(err) => return Err(err.into()),
+                Input::Stop => return Ok(()),
+            }
+        }
+    }
+}
+
+fn try_next_event(events: &Receiver<SessionEvent>) -> Result<Option<SessionEvent>, RistError> {
+    match events.try_recv() {
+        Ok(event) => Ok(Some(event)),
+        Err(TryRecvError::Empty) => Ok(None),
+        Err(TryRecvError::Disconnected) => Err(RistError::Closed),
+    }
+}
+
+fn send_to(socket: &UdpSocket, bytes: &[u8], destination: SocketAddr) -> Result<(), RistError> {
+    match socket.send_to(bytes, destination) {
+        Ok(_) => Ok(()),
+        // The datagram is lost as it would be on the network.
+        Err(err) if is_transient(&err) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
@@ -0,0 +1,377 @@
+//! I/O drivers running the sans-IO state machines over UDP sockets. Each endpoint owns two sockets: the RTP
+//! socket and the RTCP socket, or a single one in tunnel mode. The drivers only move datagrams and payloads
+//! around, everything else is left to the state machines.
+
+pub mod blocking;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Instant;
+
+use crate::common::{RistListenerPort, Transmit, MAX_DATAGRAM_SIZE};
+use crate::error::RistError;
+use crate::multicast::{self, Setup};
+use crate::receiver::{self, Receiver};
+use crate::rtp_sender::check_payload_size;
+use crate::sender::{Sender, SenderConfig};
+use crate::session::SessionEvent;
+use crate::tunnel::{self, Inner, Tunnel};
+
+/// Size of the buffers datagrams are received in. Larger datagrams are truncated and fail to parse.
+pub(crate) const RECV_BUFFER_SIZE: usize = MAX_DATAGRAM_SIZE + tunnel::OVERHEAD;
+
+/// Binds the RTP socket on the RTP source port and the RTCP socket on the RTCP source port. When the receivers
+/// are the members of a multicast group, the RTCP socket joins it to get their RTCP packets.
+pub(crate) fn bind_sender(config: &SenderConfig) -> Result<(UdpSocket, UdpSocket), RistError> {
+    let group = config.rtp_config.peer_address;
+    let unspecified = unspecified(group);
+    let setup = |join| {
+        group.is_multicast().then_some(Setup {
+            group,
+            source: None,
+            options: config.multicast.as_ref(),
+            join,
+        })
+    };
+
+    let rtp = multicast::bind(
+        SocketAddr::new(unspecified, config.rtp_config.rtp_source_port),
+        setup(false),
+    )?;
+    let rtcp = multicast::bind(
+        SocketAddr::new(unspecified, config.rtcp_config.rtcp_source_port),
+        setup(true),
+    )?;
+    Ok((rtp, rtcp))
+}
+
+/// Binds the RTP socket on `address:P` and the RTCP socket on `address:P+1`, both joining the multicast group of
+/// the configuration if any.
+pub(crate) fn bind_receiver(
+    address: IpAddr,
+    listen_port: RistListenerPort,
+    config: &receiver::Config,
+) -> Result<(UdpSocket, UdpSocket), RistError> {
+    let setup = || {
+        config.multicast.as_ref().map(|membership| Setup {
+            group: membership.group,
+            source: membership.source,
+            options: Some(&membership.options),
+            join: true,
+        })
+    };
+
+    let rtp = multicast::bind(SocketAddr::new(address, listen_port.get()), setup())?;
+    let rtcp = multicast::bind(SocketAddr::new(address, listen_port.get() + 1), setup())?;
+    Ok((rtp, rtcp))
+}
+
+/// Binds the single socket of a sender in tunnel mode on the RTP source port. Every packet goes to the port P of
+/// the receiver.
+pub(crate) fn bind_tunnel_sender(
+    config: SenderConfig,
+    tunnel: tunnel::Config,
+) -> Result<(TunnelEndpoint<SenderEndpoint>, UdpSocket), RistError> {
+    config.validate()?;
+    tunnel.validate()?;
+    let port = config.rtp_config.rtp_source_port;
+    let peer = SocketAddr::new(
+        config.rtp_config.peer_address,
+        config.rtp_config.rtp_peer_port.get(),
+    );
+    let socket = multicast::bind(SocketAddr::new(unspecified(peer.ip()), port), None)?;
+    let endpoint = SenderEndpoint {
+        rtp_port: port,
+        sender: Sender::new(config, Instant::now())?,
+    };
+    Ok((
+        TunnelEndpoint::new(endpoint, Tunnel::new(tunnel), port, Some(peer)),
+        socket,
+    ))
+}
+
+/// Binds the single socket of a receiver in tunnel mode on `address:P`. The RTCP packets go back to the address
+/// and port the sender sends from.
+pub(crate) fn bind_tunnel_receiver(
+    address: IpAddr,
+    listen_port: RistListenerPort,
+    config: receiver::Config,
+    tunnel: tunnel::Config,
+) -> Result<(TunnelEndpoint<ReceiverEndpoint>, UdpSocket), RistError> {
+    config.validate()?;
+    tunnel.validate()?;
+    let socket = multicast::bind(SocketAddr::new(address, listen_port.get()), None)?;
+    let endpoint = ReceiverEndpoint {
+        receiver: Receiver::new(listen_port, config, Instant::now())?,
+        rtp_port: listen_port.get(),
+    };
+    Ok((
+        TunnelEndpoint::new(endpoint, Tunnel::new(tunnel), listen_port.get(), None),
+        socket,
+    ))
+}
+
+/// Unspecified address of the family of `address`.
+fn unspecified(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+/// Timeouts of blocking reads, full or empty non-blocking sockets, and ICMP errors caused by a previous datagram,
+/// which some platforms report on the next read or write. None of them stops a driver.
+pub(crate) fn is_transient(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionRefused
+    )
+}
+
+/// Socket a datagram was received on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Socket {
+    Rtp,
+    Rtcp,
+}
+
+/// State machine run by a driver.
+pub(crate) trait Endpoint {
+    /// Local UDP port of the RTP socket. Transmits from any other port go through the RTCP socket.
+    fn rtp_port(&self) -> u16;
+
+    /// Malformed datagrams are reported to the caller, which drops them.
+    fn handle_input(
+        &mut self,
+        socket: Socket,
+        datagram: &[u8],
+        source: SocketAddr,
+        now: Instant,
+    ) -> Result<(), RistError>;
+
+    /// Payload pushed by the application.
+    fn push(&mut self, payload: &[u8], now: Instant) -> Result<(), RistError>;
+
+    /// Payload to deliver to the application.
+    fn poll_payload(&mut self, now: Instant) -> Option<Vec<u8>>;
+
+    fn poll_transmit(&mut self, now: Instant) -> Result<Option<Transmit>, RistError>;
+
+    fn poll_timeout(&self, now: Instant) -> Option<Instant>;
+
+    /// Session event to report to the application.
+    fn poll_event(&mut self) -> Option<SessionEvent>;
+
+    /// Error the session ended with, which stops the driver.
+    fn error(&self) -> Option<RistError>;
+}
+
+pub(crate) struct SenderEndpoint {
+    pub(crate) sender: Sender,
+    pub(crate) rtp_port: u16,
+}
+
+impl Endpoint for SenderEndpoint {
+    fn rtp_port(&self) -> u16 {
+        self.rtp_port
+    }
+
+    fn handle_input(
+        &mut self,
+        socket: Socket,
+        datagram: &[u8],
+        source: SocketAddr,
+        now: Instant,
+    ) -> Result<(), RistError> {
+        match socket {
+            Socket::Rtcp => self.sender.handle_rtcp_input(datagram, source, now),
+            // The receiver never sends RTP packets.
+            Socket::Rtp => Ok(()),
+        }
+    }
+
+    fn push(&mut self, payload: &[u8], now: Instant) -> Result<(), RistError> {
+        self.sender.push(payload, now)
+    }
+
+    fn poll_payload(&mut self, _now: Instant) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn poll_transmit(&mut self, now: Instant) -> Result<Option<Transmit>, RistError> {
+        self.sender.poll_transmit(now)
+    }
+
+    fn poll_timeout(&self, _now: Instant) -> Option<Instant> {
+        self.sender.poll_timeout()
+    }
+
+    fn poll_event(&mut self) -> Option<SessionEvent> {
+        self.sender.poll_event()
+    }
+
+    fn error(&self) -> Option<RistError> {
+        self.sender.session().error()
+    }
+}
+
+pub(crate) struct ReceiverEndpoint {
+    pub(crate) receiver: Receiver,
+    pub(crate) rtp_port: u16,
+}
+
+impl Endpoint for ReceiverEndpoint {
+    fn rtp_port(&self) -> u16 {
+        self.rtp_port
+    }
+
+    fn handle_input(
+        &mut self,
+        socket: Socket,
+        datagram: &[u8],
+        source: SocketAddr,
+        now: Instant,
+    ) -> Result<(), RistError> {
+        match socket {
+            Socket::Rtp => self
+                .receiver
+                .handle_rtp_input(datagram, source, now)
+                .map(|_| ()),
+            Socket::Rtcp => self.receiver.handle_rtcp_input(datagram, source, now),
+        }
+    }
+
+    fn push(&mut self, _payload: &[u8], _now: Instant) -> Result<(), RistError> {
+        Ok(())
+    }
+
+    fn poll_payload(&mut self, now: Instant) -> Option<Vec<u8>> {
+        self.receiver.poll_payload(now)
+    }
+
+    fn poll_transmit(&mut self, now: Instant) -> Result<Option<Transmit>, RistError> {
+        // RTCP packets are dropped until the sender is known.
+        while let Some(bytes) = self.receiver.poll_rtcp_transmit(now)? {
+            if let Some(destination) = self.receiver.rtcp_destination() {
+                return Ok(Some(Transmit {
+                    destination,
+                    source_port: self.rtp_port + 1,
+                    bytes,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn poll_timeout(&self, now: Instant) -> Option<Instant> {
+        self.receiver.poll_timeout(now)
+    }
+
+    fn poll_event(&mut self) -> Option<SessionEvent> {
+        self.receiver.poll_event()
+    }
+
+    fn error(&self) -> Option<RistError> {
+        self.receiver.session().error()
+    }
+}
+
+/// Endpoint run over a single UDP socket, its RTP and RTCP packets multiplexed in a GRE tunnel.
+pub(crate) struct TunnelEndpoint<E> {
+    inner: E,
+    tunnel: Tunnel,
+
+    /// Local UDP port of the tunnel.
+    port: u16,
+
+    /// Remote end of the tunnel. `None` keeps the destinations chosen by the inner endpoint, for a receiver
+    /// learning the address of the sender.
+    peer: Option<SocketAddr>,
+
+    /// Destination of the last transmit, where the keep-alives go when the peer is not fixed.
+    last_destination: Option<SocketAddr>,
+}
+
+impl<E: Endpoint> TunnelEndpoint<E> {
+    pub(crate) fn new(inner: E, tunnel: Tunnel, port: u16, peer: Option<SocketAddr>) -> Self {
+        Self {
+            inner,
+            tunnel,
+            port,
+            peer,
+            last_destination: None,
+        }
+    }
+}
+
+impl<E: Endpoint> Endpoint for TunnelEndpoint<E> {
+    fn rtp_port(&self) -> u16 {
+        self.port
+    }
+
+    fn handle_input(
+        &mut self,
+        _socket: Socket,
+        datagram: &[u8],
+        source: SocketAddr,
+        now: Instant,
+    ) -> Result<(), RistError> {
+        match self.tunnel.decapsulate(datagram)? {
+            Inner::Rtp(packet) => self.inner.handle_input(Socket::Rtp, packet, source, now),
+            Inner::Rtcp(packet) => self.inner.handle_input(Socket::Rtcp, packet, source, now),
+            Inner::KeepAlive(_) => Ok(()),
+        }
+    }
+
+    fn push(&mut self, payload: &[u8], now: Instant) -> Result<(), RistError> {
+        check_payload_size(payload, tunnel::MAX_PAYLOAD_SIZE)?;
+        self.inner.push(payload, now)
+    }
+
+    fn poll_payload(&mut self, now: Instant) -> Option<Vec<u8>> {
+        self.inner.poll_payload(now)
+    }
+
+    fn poll_transmit(&mut self, now: Instant) -> Result<Option<Transmit>, RistError> {
+        if let Some(transmit) = self.inner.poll_transmit(now)? {
+            let is_rtcp = transmit.source_port != self.inner.rtp_port();
+            let destination = self.peer.unwrap_or(transmit.destination);
+            self.last_destination = Some(destination);
+            return Ok(Some(Transmit {
+                destination,
+                source_port: self.port,
+                bytes: self.tunnel.encapsulate(&transmit.bytes, is_rtcp)?,
+            }));
+        }
+
+        // No keep-alive until the peer is known.
+        let Some(destination) = self.peer.or(self.last_destination) else {
+            return Ok(None);
+        };
+        Ok(self.tunnel.poll_keepalive(now)?.map(|bytes| Transmit {
+            destination,
+            source_port: self.port,
+            bytes,
+        }))
+    }
+
+    fn poll_timeout(&self, now: Instant) -> Option<Instant> {
+        match (self.inner.poll_timeout(now), self.tunnel.poll_timeout()) {
+            (Some(inner), Some(keepalive)) => Some(inner.min(keepalive)),
+            (inner, keepalive) => inner.or(keepalive),
+        }
+    }
+
+    fn poll_event(&mut self) -> Option<SessionEvent> {
+        self.inner.poll_event()
+    }
+
+    fn error(&self) -> Option<RistError> {
+        self.inner.error()
+    }
+}
@@ -0,0 +1,368 @@
+//! Async driver, running each endpoint in a tokio task.
+
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use ::tokio::net::UdpSocket;
+use ::tokio::sync::mpsc::{self, error::TrySendError};
+use ::tokio::task::JoinHandle;
+
+use super::{
+    bind_receiver, bind_sender, bind_tunnel_receiver, bind_tunnel_sender, is_transient, Endpoint,
+    ReceiverEndpoint, SenderEndpoint, Socket, RECV_BUFFER_SIZE,
+};
+use crate::common::RistListenerPort;
+use crate::error::{BufferOverflow, RistError};
+use crate::receiver::{self, Receiver};
+use crate::rtp_sender::{check_payload_size, MAX_PAYLOAD_SIZE};
+use crate::sender::{Sender, SenderConfig};
+use crate::session::SessionEvent;
+use crate::tunnel;
+#[cfg(feature = "upnp")]
+use crate::upnp::{self, PortMapping};
+
+/// Number of payloads queued between the application and the driver task.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// RIST sender sending its media from a tokio task. The task stops when the sender is dropped, or on the first
+/// error, see [`TokioSender::closed`].
+pub struct TokioSender {
+    payloads: mpsc::Sender<Vec<u8>>,
+    events: mpsc::UnboundedReceiver<SessionEvent>,
+    task: JoinHandle<Result<(), RistError>>,
+
+    /// Largest payload accepted, smaller in tunnel mode to leave room for the GRE header.
+    max_payload_size: usize,
+}
+
+impl TokioSender {
+    /// Binds the RTP socket on the RTP source port and the RTCP socket on the RTCP source port, then
+    /// starts sending to the receiver.
+    pub async fn bind(config: SenderConfig) -> Result<Self, RistError> {
+        config.validate()?;
+        let (rtp, rtcp) = bind_sender(&config)?;
+        let endpoint = SenderEndpoint {
+            rtp_port: config.rtp_config.rtp_source_port,
+            sender: Sender::new(config, Instant::now())?,
+        };
+
+        Self::spawn(
+            endpoint,
+            into_tokio(rtp)?,
+            Some(into_tokio(rtcp)?),
+            MAX_PAYLOAD_SIZE,
+        )
+    }
+
+    /// Binds a single socket on the RTP source port, then starts sending to the receiver through a GRE tunnel
+    /// to its port P. The RTCP source port of the configuration is not used.
+    pub async fn bind_tunnel(
+        config: SenderConfig,
+        tunnel: tunnel::Config,
+    ) -> Result<Self, RistError> {
+        let (endpoint, socket) = bind_tunnel_sender(config, tunnel)?;
+        Self::spawn(
+            endpoint,
+            into_tokio(socket)?,
+            None,
+            tunnel::MAX_PAYLOAD_SIZE,
+        )
+    }
+
+    fn spawn<E: Endpoint + Send + 'static>(
+        endpoint: E,
+        rtp: UdpSocket,
+        rtcp: Option<UdpSocket>,
+        max_payload_size: usize,
+    ) -> Result<Self, RistError> {
+        let (payloads, payloads_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let task = ::tokio::spawn(run(endpoint, rtp, rtcp, Some(payloads_rx), None, events_tx));
+
+        Ok(Self {
+            payloads,
+            events,
+            task,
+            max_payload_size,
+        })
+    }
+
+    /// Queues a media payload, typically 7 MPEG-2 TS packets of 188 bytes.
+    pub async fn send(&self, payload: &[u8]) -> Result<(), RistError> {
+        check_payload_size(payload, self.max_payload_size)?;
+        self.payloads
+            .send(payload.to_vec())
+            .await
+            .map_err(|_| RistError::Closed)
+    }
+
+    /// Like [`TokioSender::send`], but fails instead of waiting when the queue is full.
+    pub fn try_send(&self, payload: &[u8]) -> Result<(), RistError> {
+        check_payload_size(payload, self.max_payload_size)?;
+        self.payloads
+            .try_send(payload.to_vec())
+            .map_err(|err| match err {
+                TrySendError::Full(_) => RistError::BufferOverflow(BufferOverflow::QueueFull),
+                TrySendError::Closed(_) => RistError::Closed,
+            })
+    }
+
+    /// Next change of the session with the receiver.
+    pub async fn next_event(&mut self) -> Result<SessionEvent, RistError> {
+        self.events.recv().await.ok_or(RistError::Closed)
+    }
+
+    /// Waits for the driver task to stop, and returns why: `RistError::PeerTimeout` when the receiver went silent,
+    /// or the I/O error that ended it.
+    pub async fn closed(mut self) -> RistError {
+        join(&mut self.task).await
+    }
+}
+
+impl Drop for TokioSender {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// RIST receiver receiving the media in a tokio task. The task stops when the receiver is dropped, or on the
+/// first error, see [`TokioReceiver::closed`].
+pub struct TokioReceiver {
+    payloads: mpsc::Receiver<Vec<u8>>,
+
+    /// Payloads dropped because the queue was full.
+    dropped: Arc<AtomicU64>,
+    events: mpsc::UnboundedReceiver<SessionEvent>,
+    task: JoinHandle<Result<(), RistError>>,
+
+    #[cfg(feature = "upnp")]
+    listen_port: RistListenerPort,
+    #[cfg(feature = "upnp")]
+    port_mapping: Option<PortMapping>,
+}
+
+impl TokioReceiver {
+    /// Binds the RTP socket on `address:P` and the RTCP socket on `address:P+1`, then starts receiving.
+    pub async fn bind(
+        address: IpAddr,
+        listen_port: RistListenerPort,
+        config: receiver::Config,
+    ) -> Result<Self, RistError> {
+        config.validate()?;
+        let (rtp, rtcp) = bind_receiver(address, listen_port, &config)?;
+        let endpoint = ReceiverEndpoint {
+            receiver: Receiver::new(listen_port, config, Instant::now())?,
+            rtp_port: listen_port.get(),
+        };
+
+        Self::spawn(
+            endpoint,
+            into_tokio(rtp)?,
+            Some(into_tokio(rtcp)?),
+            listen_port,
+        )
+    }
+
+    /// Binds a single socket on `address:P`, then starts receiving through a GRE tunnel.
+    pub async fn bind_tunnel(
+        address: IpAddr,
+        listen_port: RistListenerPort,
+        config: receiver::Config,
+        tunnel: tunnel::Config,
+    ) -> Result<Self, RistError> {
+        let (endpoint, socket) = bind_tunnel_receiver(address, listen_port, config, tunnel)?;
+        Self::spawn(endpoint, into_tokio(socket)?, None, listen_port)
+    }
+
+    fn spawn<E: Endpoint + Send + 'static>(
+        endpoint: E,
+        rtp: UdpSocket,
+        rtcp: Option<UdpSocket>,
+        #[cfg_attr(not(feature = "upnp"), allow(unused_variables))] listen_port: RistListenerPort,
+    ) -> Result<Self, RistError> {
+        let (payloads_tx, payloads) = mpsc::channel(CHANNEL_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let task = ::tokio::spawn(run(
+            endpoint,
+            rtp,
+            rtcp,
+            None,
+            Some((payloads_tx, dropped.clone())),
+            events_tx,
+        ));
+
+        Ok(Self {
+            payloads,
+            dropped,
+            events,
+            task,
+            #[cfg(feature = "upnp")]
+            listen_port,
+            #[cfg(feature = "upnp")]
+            port_mapping: None,
+        })
+    }
+
+    /// Asks the gateway of the local network to forward P and P+1 to this receiver. The mappings are renewed
+    /// until the receiver is dropped, then removed on a blocking thread of the runtime.
+    #[cfg(feature = "upnp")]
+    pub async fn map_ports(&mut self, config: upnp::Config) -> Result<&PortMapping, RistError> {
+        let listen_port = self.listen_port;
+        let port_mapping =
+            ::tokio::task::spawn_blocking(move || PortMapping::map(listen_port, config))
+                .await
+                .map_err(|_| RistError::Closed)??;
+        Ok(self.port_mapping.insert(port_mapping))
+    }
+
+    /// Next media payload, in sequence order, once the buffer latency elapsed. Payloads are dropped while the
+    /// queue is full, so that a slow application does not hold the protocol back.
+    pub async fn recv(&mut self) -> Result<Vec<u8>, RistError> {
+        self.payloads.recv().await.ok_or(RistError::Closed)
+    }
+
+    /// Number of payloads dropped because the application did not take them in time.
+    pub fn dropped_payloads(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Next change of the session with the sender.
+    pub async fn next_event(&mut self) -> Result<SessionEvent, RistError> {
+        self.events.recv().await.ok_or(RistError::Closed)
+    }
+
+    /// Waits for the driver task to stop, and returns why: `RistError::PeerTimeout` when the sender went silent,
+    /// or the I/O error that ended it.
+    pub async fn closed(mut self) -> RistError {
+        join(&mut self.task).await
+    }
+}
+
+impl Drop for TokioReceiver {
+    fn drop(&mut self) {
+        self.task.abort();
+        // Removing the mappings waits for the gateway, which would stall the runtime worker dropping the receiver.
+        #[cfg(feature = "upnp")]
+        if let Some(port_mapping) = self.port_mapping.take() {
+            if let Ok(runtime) = ::tokio::runtime::Handle::try_current() {
+                runtime.spawn_blocking(move || drop(port_mapping));
+            }
+        }
+    }
+}
+
+/// - `rtcp` is `None` in tunnel mode, where every packet goes through the RTP socket.
+/// - `payloads_out` counts the payloads dropped because the application did not take them in time.
+async fn run<E: Endpoint>(
+    mut endpoint: E,
+    rtp: UdpSocket,
+    rtcp: Option<UdpSocket>,
+    mut payloads_in: Option<mpsc::Receiver<Vec<u8>>>,
+    payloads_out: Option<(mpsc::Sender<Vec<u8>>, Arc<AtomicU64>)>,
+    events: mpsc::UnboundedSender<SessionEvent>,
+) -> Result<(), RistError> {
+    let mut rtp_buf = vec![0; RECV_BUFFER_SIZE];
+    let mut rtcp_buf = vec![0; RECV_BUFFER_SIZE];
+
+    loop {
+        let now = Instant::now();
+        while let Some(transmit) = endpoint.poll_transmit(now)? {
+            let socket = match &rtcp {
+                Some(rtcp) if transmit.source_port != endpoint.rtp_port() => rtcp,
+                _ => &rtp,
+            };
+            match socket.send_to(&transmit.bytes, transmit.destination).await {
+                Ok(_) => {}
+                // The datagram is lost as it would be on the network.
+                Err(err) if is_transient(&err) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        while let Some(event) = endpoint.poll_event() {
+            // Nobody listens to the events once the application dropped its end.
+            let _ = events.send(event);
+        }
+        // Stops once the application got the event closing the session.
+        if let Some(err) = endpoint.error() {
+            return Err(err);
+        }
+        if let Some((payloads_out, dropped)) = &payloads_out {
+            while let Some(payload) = endpoint.poll_payload(now) {
+                // Waiting for the application would stall the reads, the NACKs and the reports.
+                match payloads_out.try_send(payload) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(TrySendError::Closed(_)) => return Ok(()),
+                }
+            }
+        }
+
+        let timeout = endpoint.poll_timeout(now);
+        let sleep = async {
+            match timeout {
+                Some(timeout) => ::tokio::time::sleep_until(timeout.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        ::tokio::select! {
+            payload = recv_payload(&mut payloads_in) => match payload {
+                Some(payload) => endpoint.push(&payload, Instant::now())?,
+                None => return Ok(()),
+            },
+            received = rtp.recv_from(&mut rtp_buf) => match received {
+                // Malformed datagrams are dropped, they shall not stop the flow.
+                Ok((len, source)) => {
+                    let _ = endpoint.handle_input(Socket::Rtp, &rtp_buf[..len], source, Instant::now());
+                }
+                Err(err) if is_transient(&err) => {}
+                Err(err) => return Err(err.into()),
+            },
+            received = recv_from(&rtcp, &mut rtcp_buf) => match received {
+                Ok((len, source)) => {
+                    let _ = endpoint.handle_input(Socket::Rtcp, &rtcp_buf[..len], source, Instant::now());
+                }
+                Err(err) if is_transient(&err) => {}
+                Err(err) => return Err(err.into()),
+            },
+            _ = sleep => {}
+        }
+    }
+}
+
+/// `RistError::Closed` if the task stopped without error, or panicked.
+async fn join(task: &mut JoinHandle<Result<(), RistError>>) -> RistError {
+    match task.await {
+        Ok(Err(err)) => err,
+        _ => RistError::Closed,
+    }
+}
+
+fn into_tokio(socket: std::net::UdpSocket) -> Result<UdpSocket, RistError> {
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket)?)
+}
+
+/// Never resolves when the endpoint takes no payload from the application.
+async fn recv_payload(payloads: &mut Option<mpsc::Receiver<Vec<u8>>>) -> Option<Vec<u8>> {
+    match payloads {
+        Some(payloads) => payloads.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Never resolves without a socket.
+async fn recv_from(
+    socket: &Option<UdpSocket>,
+    buf: &mut [u8],
+) -> std::io::Result<(usize, std::net::SocketAddr)> {
+    match socket {
+        Some(socket) => socket.recv_from(buf).await,
+        None => std::future::pending().await,
+    }
+}
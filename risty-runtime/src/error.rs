@@ -0,0 +1,109 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use risty_core::{MarshalError, ParseError};
+
+/// Errors of the RIST runtime.
+#[derive(Debug, thiserror::Error)]
+pub enum RistError {
+    #[error("invalid configuration: {0}")]
+    Config(#[from] ConfigError),
+
+    #[error("failed to bind {address}: {source}")]
+    Bind {
+        address: SocketAddr,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("socket error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The peer did not send any RTCP packet for the given duration.
+    #[error("no RTCP packet received from the peer for {0:?}")]
+    PeerTimeout(Duration),
+
+    /// A packet received from the peer does not follow the protocol.
+    #[error("protocol violation: {0}")]
+    Protocol(#[from] ParseError),
+
+    #[error(transparent)]
+    Marshal(#[from] MarshalError),
+
+    /// A packet came from an address other than the one of the sender, it was dropped.
+    #[error("packet from {0}, which is not the sender")]
+    UnexpectedSource(SocketAddr),
+
+    #[cfg(feature = "upnp")]
+    #[error("UPnP port mapping failed: {0}")]
+    Upnp(#[from] crate::upnp::UpnpError),
+
+    /// A payload or a queue does not fit in the space available for it.
+    #[error("buffer overflow: {0}")]
+    BufferOverflow(BufferOverflow),
+
+    /// The driver stopped, after an error or because its other half was dropped.
+    #[error("the driver is stopped")]
+    Closed,
+}
+
+/// Invalid configuration values, detected when a configuration or an endpoint is created.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ConfigError {
+    #[error("port {0} is odd, RIST listener ports shall be even")]
+    OddPort(u16),
+
+    #[error("port {0} is out of the 2..=65534 range")]
+    PortOutOfRange(u16),
+
+    #[error("{name} shall not be zero")]
+    Zero { name: &'static str },
+
+    #[error("the reorder section ({reorder_section:?}) shall be shorter than the buffer size ({buffer_size:?})")]
+    ReorderSectionTooLong {
+        reorder_section: Duration,
+        buffer_size: Duration,
+    },
+
+    #[error("the RTCP interval ({0:?}) shall be 100ms or less")]
+    RtcpIntervalTooLong(Duration),
+
+    #[error("the stale timeout ({stale_timeout:?}) shall be longer than the RTCP interval ({rtcp_interval:?})")]
+    StaleTimeoutTooShort {
+        stale_timeout: Duration,
+        rtcp_interval: Duration,
+    },
+
+    #[error("the peer timeout ({peer_timeout:?}) shall be longer than the stale timeout ({stale_timeout:?})")]
+    PeerTimeoutTooShort {
+        peer_timeout: Duration,
+        stale_timeout: Duration,
+    },
+
+    #[error("{0} is not a multicast address")]
+    NotMulticast(IpAddr),
+
+    #[error("the address family does not match the one of the multicast group {0}")]
+    AddressFamilyMismatch(IpAddr),
+
+    #[error("source-specific multicast is not supported for the IPv6 group {0} on this platform")]
+    UnsupportedSourceSpecificMulticast(IpAddr),
+
+    #[error("multicast TTL {0} is out of the 1..=255 range")]
+    TtlOutOfRange(u32),
+
+    #[error("a multicast sender shall send its RTCP packets from port {expected}, the receivers send theirs to it, not {port}")]
+    MulticastRtcpPort { port: u16, expected: u16 },
+
+    #[error("the CNAME is {0} bytes long, it shall not exceed 255 bytes")]
+    CnameTooLong(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum BufferOverflow {
+    #[error("payload of {size} bytes exceeds the maximum of {max} bytes")]
+    PayloadTooLarge { size: usize, max: usize },
+
+    #[error("the payload queue is full")]
+    QueueFull,
+}
@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+
+/// Estimates the interarrival jitter of a source as described in RFC 3550 Appendix A.8: the mean deviation
+/// of the difference in packet spacing at the receiver compared to the sender, smoothed with a gain of 1/16.
+#[derive(Debug, Clone)]
+pub struct JitterEstimator {
+    /// Frequency of the RTP timestamp clock, in Hz.
+    clock_rate: u32,
+
+    /// Arrival time of the first packet, arrival times are converted to timestamp units relative to it.
+    anchor: Option<Instant>,
+
+    /// Relative transit time of the previous packet.
+    transit: Option<u32>,
+
+    /// Jitter estimate in timestamp units, scaled by 16 to keep the precision of the integer computation.
+    jitter: u64,
+}
+
+impl JitterEstimator {
+    pub fn new(clock_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            anchor: None,
+            transit: None,
+            jitter: 0,
+        }
+    }
+
+    /// Shall be called for each original RTP data packet, retransmitted packets would skew the estimate.
+    pub fn update(&mut self, rtp_timestamp: u32, arrival: Instant) {
+        let anchor = *self.anchor.get_or_insert(arrival);
+        let elapsed = arrival.saturating_duration_since(anchor);
+        let arrival = (elapsed.as_nanos() * self.clock_rate as u128 / 1_000_000_000) as u32;
+
+        let transit = arrival.wrapping_sub(rtp_timestamp);
+        if let Some(previous) = self.transit.replace(transit) {
+            let d = (transit.wrapping_sub(previous) as i32).unsigned_abs() as u64;
+            self.jitter = self.jitter + d - ((self.jitter + 8) >> 4);
+        }
+    }
+
+    /// The jitter in timestamp units, as carried in report blocks.
+    pub fn jitter(&self) -> u32 {
+        (self.jitter >> 4) as u32
+    }
+
+    pub fn jitter_duration(&self) -> Duration {
+        Duration::from_nanos(self.jitter() as u64 * 1_000_000_000 / self.clock_rate as u64)
+    }
+
+    /// Forgets the previous transit time, to be used when the sender restarts its sequence.
+    pub fn reset(&mut self) {
+        self.anchor = None;
+        self.transit = None;
+        self.jitter = 0;
+    }
+}
@@ -1,4 +1,23 @@
-mod common;
-mod receiver;
-mod rtcp;
-mod sender;
+pub mod common;
+pub mod driver;
+mod error;
+mod jitter;
+mod loss;
+pub mod multicast;
+pub mod nack_scheduler;
+pub mod receive_buffer;
+pub mod receiver;
+pub mod retransmission;
+pub mod rtcp;
+pub mod rtcp_sender;
+pub mod rtp_sender;
+pub mod rtt;
+pub mod sender;
+mod sequence;
+pub mod session;
+pub mod stats;
+pub mod tunnel;
+#[cfg(feature = "upnp")]
+pub mod upnp;
+
+pub use error::{BufferOverflow, ConfigError, RistError};
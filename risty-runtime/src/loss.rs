@@ -0,0 +1,163 @@
+/// Largest positive value of the 24-bit signed cumulative number of packets lost.
+const MAX_CUMULATIVE_LOST: i64 = 0x7FFFFF;
+
+/// Smallest negative value of the 24-bit signed cumulative number of packets lost. Duplicates can make
+/// the count negative.
+const MIN_CUMULATIVE_LOST: i64 = -0x800000;
+
+/// Loss figures for one report interval, ready to be put in a report block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LossReport {
+    /// Fraction of packets lost during the interval, a fixed point number with the binary point at the left.
+    pub fraction_lost: u8,
+
+    /// Cumulative number of packets lost, as a 24-bit two's complement number.
+    pub cumm_packets_lost: u32,
+}
+
+/// Counts the packets lost by the receiver as described in RFC 3550 Appendix A.3, both before and after
+/// retransmission. The report blocks carry the losses after retransmission, so that they reflect what RIST
+/// actually recovered.
+#[derive(Debug, Clone, Default)]
+pub struct LossAccounting {
+    /// Original packets received, with SSRC LSB=0.
+    received_original: u64,
+
+    /// Retransmitted packets that filled a gap, with SSRC LSB=1.
+    recovered: u64,
+
+    expected_prior: u64,
+    received_prior: u64,
+}
+
+impl LossAccounting {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_original(&mut self) {
+        self.received_original += 1;
+    }
+
+    /// Shall only be called for retransmitted packets that were still missing, not for duplicates.
+    pub fn record_recovered(&mut self) {
+        self.recovered += 1;
+    }
+
+    /// Packets recovered thanks to retransmissions.
+    pub fn recovered(&self) -> u64 {
+        self.recovered
+    }
+
+    /// Packets lost on the network, before any retransmission.
+    /// - `expected` is the number of packets expected since the sequence started.
+    pub fn lost_before_retransmission(&self, expected: u64) -> i64 {
+        expected as i64 - self.received_original as i64
+    }
+
+    /// Packets still lost after retransmissions.
+    /// - `expected` is the number of packets expected since the sequence started.
+    pub fn lost_after_retransmission(&self, expected: u64) -> i64 {
+        expected as i64 - self.received() as i64
+    }
+
+    /// Computes the loss figures since the previous report and starts a new report interval.
+    /// - `expected` is the number of packets expected since the sequence started.
+    pub fn report(&mut self, expected: u64) -> LossReport {
+        let received = self.received();
+
+        let expected_interval = expected.saturating_sub(self.expected_prior);
+        self.expected_prior = expected;
+        let received_interval = received.saturating_sub(self.received_prior);
+        self.received_prior = received;
+
+        let lost_interval = expected_interval as i64 - received_interval as i64;
+        let fraction_lost = if expected_interval == 0 || lost_interval <= 0 {
+            0
+        } else {
+            ((lost_interval << 8) / expected_interval as i64).min(u8::MAX as i64) as u8
+        };
+
+        let lost = self
+            .lost_after_retransmission(expected)
+            .clamp(MIN_CUMULATIVE_LOST, MAX_CUMULATIVE_LOST);
+
+        LossReport {
+            fraction_lost,
+            cumm_packets_lost: (lost as u32) & 0xFFFFFF,
+        }
+    }
+
+    /// The sender restarted its sequence, the counts start over.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn received(&self) -> u64 {
+        self.received_original + self.recovered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounting(originals: u64, recovered: u64) -> LossAccounting {
+        LossAccounting {
+            received_original: originals,
+            recovered,
+            ..LossAccounting::default()
+        }
+    }
+
+    #[test]
+    fn fraction_lost_covers_the_interval_only() {
+        let mut accounting = accounting(70, 5);
+        assert_eq!(accounting.lost_before_retransmission(100), 30);
+        assert_eq!(
+            accounting.report(100),
+            LossReport {
+                fraction_lost: 64,
+                cumm_packets_lost: 25
+            }
+        );
+
+        for _ in 0..100 {
+            accounting.record_original();
+        }
+        assert_eq!(
+            accounting.report(200),
+            LossReport {
+                fraction_lost: 0,
+                cumm_packets_lost: 25
+            }
+        );
+
+        // Everything lost during the interval.
+        assert_eq!(accounting.report(250).fraction_lost, 255);
+    }
+
+    #[test]
+    fn duplicates_make_the_loss_negative() {
+        let mut accounting = accounting(12, 0);
+        assert_eq!(
+            accounting.report(10),
+            LossReport {
+                fraction_lost: 0,
+                cumm_packets_lost: 0xFFFFFE
+            }
+        );
+    }
+
+    #[test]
+    fn cumulative_loss_is_clamped_to_24_bits() {
+        assert_eq!(
+            accounting(0, 0).report(0x900000).cumm_packets_lost,
+            0x7FFFFF
+        );
+        assert_eq!(
+            accounting(0x900000, 0).report(0).cumm_packets_lost,
+            0x800000
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Multicast transport. A sender targeting a multicast group sends its RTP packets to the group on port P and
+//! its RTCP packets to the group on port P+1. The receivers join the group on both ports and send their RTCP
+//! packets to the group on port P+1, where the sender listens. Retransmissions go to the group as well, so one
+//! NACK can repair the loss for every receiver.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::error::{ConfigError, RistError};
+
+/// Local interface the multicast packets are sent and received on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interface {
+    /// Address of the interface.
+    V4(Ipv4Addr),
+
+    /// Index of the interface.
+    V6(u32),
+}
+
+pub struct Options {
+    /// Number of hops the packets sent to the group live for, 1 keeps them on the local network.
+    pub ttl: u32,
+
+    /// `None` lets the system choose the interface.
+    pub interface: Option<Interface>,
+
+    /// Whether the packets sent to the group are looped back to the local host, needed when senders and
+    /// receivers share a host.
+    pub loopback: bool,
+}
+
+impl Options {
+    pub fn validate(&self, group: IpAddr) -> Result<(), ConfigError> {
+        if !(1..=255).contains(&self.ttl) {
+            return Err(ConfigError::TtlOutOfRange(self.ttl));
+        }
+        match (self.interface, group) {
+            (None, _)
+            | (Some(Interface::V4(_)), IpAddr::V4(_))
+            | (Some(Interface::V6(_)), IpAddr::V6(_)) => Ok(()),
+            _ => Err(ConfigError::AddressFamilyMismatch(group)),
+        }
+    }
+}
+
+/// Group a receiver joins.
+pub struct Membership {
+    pub group: IpAddr,
+
+    /// Source-specific multicast: only the packets sent by this address are received. IPv6 groups are only
+    /// supported on Linux, Android, Apple platforms and FreeBSD.
+    pub source: Option<IpAddr>,
+
+    pub options: Options,
+}
+
+impl Membership {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !self.group.is_multicast() {
+            return Err(ConfigError::NotMulticast(self.group));
+        }
+        match (self.source, self.group) {
+            (None, _) | (Some(IpAddr::V4(_)), IpAddr::V4(_)) => {}
+            (Some(IpAddr::V6(_)), IpAddr::V6(_)) if SSM_V6 => {}
+            (Some(IpAddr::V6(_)), IpAddr::V6(_)) => {
+                return Err(ConfigError::UnsupportedSourceSpecificMulticast(self.group))
+            }
+            _ => return Err(ConfigError::AddressFamilyMismatch(self.group)),
+        }
+        self.options.validate(self.group)
+    }
+}
+
+/// How a socket takes part in a multicast group.
+pub(crate) struct Setup<'a> {
+    pub(crate) group: IpAddr,
+    pub(crate) source: Option<IpAddr>,
+
+    /// `None` keeps the defaults of the system.
+    pub(crate) options: Option<&'a Options>,
+
+    /// Whether the socket receives the packets sent to the group, or only sends to it.
+    pub(crate) join: bool,
+}
+
+/// Binds a UDP socket on `address`. A socket joining a group shares its port with the other sockets of the host
+/// joining it, each of them receives a copy of the packets sent to the group.
+pub(crate) fn bind(address: SocketAddr, multicast: Option<Setup>) -> Result<UdpSocket, RistError> {
+    let bind_error = |source| RistError::Bind { address, source };
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )
+    .map_err(bind_error)?;
+    if multicast.as_ref().is_some_and(|setup| setup.join) {
+        socket.set_reuse_address(true).map_err(bind_error)?;
+    }
+    socket.bind(&address.into()).map_err(bind_error)?;
+
+    if let Some(setup) = multicast {
+        if let Some(options) = setup.options {
+            set_options(&socket, setup.group, options)?;
+        }
+        if setup.join {
+            join(&socket, &setup)?;
+        }
+    }
+
+    Ok(socket.into())
+}
+
+fn set_options(socket: &Socket, group: IpAddr, options: &Options) -> Result<(), RistError> {
+    match group {
+        IpAddr::V4(_) => {
+            socket.set_multicast_ttl_v4(options.ttl)?;
+            socket.set_multicast_loop_v4(options.loopback)?;
+            if let Some(Interface::V4(interface)) = options.interface {
+                socket.set_multicast_if_v4(&interface)?;
+            }
+        }
+        IpAddr::V6(_) => {
+            socket.set_multicast_hops_v6(options.ttl)?;
+            socket.set_multicast_loop_v6(options.loopback)?;
+            if let Some(Interface::V6(interface)) = options.interface {
+                socket.set_multicast_if_v6(interface)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn join(socket: &Socket, setup: &Setup) -> Result<(), RistError> {
+    let interface = setup.options.and_then(|options| options.interface);
+    match (setup.group, setup.source) {
+        (IpAddr::V4(group), source) => {
+            let interface = match interface {
+                Some(Interface::V4(interface)) => interface,
+                _ => Ipv4Addr::UNSPECIFIED,
+            };
+            match source {
+                Some(IpAddr::V4(source)) => socket.join_ssm_v4(&source, &group, &interface)?,
+                _ => socket.join_multicast_v4(&group, &interface)?,
+            }
+        }
+        (IpAddr::V6(group), source) => {
+            let interface = match interface {
+                Some(Interface::V6(interface)) => interface,
+                _ => 0,
+            };
+            match source {
+                Some(IpAddr::V6(source)) => join_ssm_v6(socket, &source, &group, interface)?,
+                _ => socket.join_multicast_v6(&group, interface)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether source-specific multicast is supported for IPv6 groups, which socket2 does not implement.
+const SSM_V6: bool = cfg!(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+));
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+))]
+fn join_ssm_v6(
+    socket: &Socket,
+    source: &Ipv6Addr,
+    group: &Ipv6Addr,
+    interface: u32,
+) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    fn storage(address: &Ipv6Addr) -> libc::sockaddr_storage {
+        let address = socket2::SockAddr::from(std::net::SocketAddrV6::new(*address, 0, 0, 0));
+        // SAFETY: an all-zero sockaddr_storage is valid, and the address fits in it.
+        unsafe {
+            let mut storage: libc::sockaddr_storage = std::mem::zeroed();
+            std::ptr::copy_nonoverlapping(
+                address.as_ptr().cast::<u8>(),
+                std::ptr::addr_of_mut!(storage).cast::<u8>(),
+                address.len() as usize,
+            );
+            storage
+        }
+    }
+
+    // SAFETY: an all-zero group_source_req is valid.
+    let mut request: libc::group_source_req = unsafe { std::mem::zeroed() };
+    request.gsr_interface = interface;
+    request.gsr_group = storage(group);
+    request.gsr_source = storage(source);
+    // SAFETY: the option value points to a group_source_req of the given size, which outlives the call.
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IPV6,
+            libc::MCAST_JOIN_SOURCE_GROUP,
+            std::ptr::addr_of!(request).cast(),
+            std::mem::size_of::<libc::group_source_req>() as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Rejected by [`Membership::validate`], never called.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+)))]
+fn join_ssm_v6(
+    _socket: &Socket,
+    _source: &Ipv6Addr,
+    _group: &Ipv6Addr,
+    _interface: u32,
+) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn membership(group: &str, source: &str) -> Membership {
+        Membership {
+            group: group.parse().unwrap(),
+            source: Some(source.parse().unwrap()),
+            options: Options {
+                ttl: 1,
+                interface: None,
+                loopback: false,
+            },
+        }
+    }
+
+    #[test]
+    fn source_shall_match_the_family_of_the_group() {
+        assert!(membership("232.1.1.1", "10.0.0.1").validate().is_ok());
+        assert!(matches!(
+            membership("232.1.1.1", "fd00::1").validate(),
+            Err(ConfigError::AddressFamilyMismatch(_))
+        ));
+        assert!(matches!(
+            membership("ff3e::1", "10.0.0.1").validate(),
+            Err(ConfigError::AddressFamilyMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn ipv6_source_specific_multicast() {
+        let result = membership("ff3e::1", "fd00::1").validate();
+        if SSM_V6 {
+            assert!(result.is_ok());
+        } else {
+            assert!(matches!(
+                result,
+                Err(ConfigError::UnsupportedSourceSpecificMulticast(_))
+            ));
+        }
+    }
+}
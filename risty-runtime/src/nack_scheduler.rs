@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::receive_buffer::ReceiveBuffer;
+
+/// Round-trip time assumed until a measurement is available.
+const DEFAULT_RTT: Duration = Duration::from_millis(50);
+
+/// Number of finished requests whose history is kept.
+const HISTORY_SIZE: usize = 1024;
+
+/// Why a packet is no longer requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NackOutcome {
+    /// The packet arrived, from a retransmission or late from the original stream.
+    Recovered(Instant),
+
+    /// The packet is past its release time in the buffer, it would be dropped if it arrived.
+    Expired(Instant),
+
+    /// The packet was requested the maximum number of times without arriving.
+    RetriesExhausted(Instant),
+}
+
+/// Retransmission requests made for a missing packet, for debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryHistory {
+    /// Instant the packet left the reorder section and became eligible for retransmission.
+    pub lost_at: Instant,
+
+    /// Instants the packet was requested at.
+    pub requests: Vec<Instant>,
+
+    /// `None` while the packet is still requested.
+    pub outcome: Option<NackOutcome>,
+}
+
+struct PendingNack {
+    history: RetryHistory,
+
+    /// Instant the packet is requested at next, if it did not arrive by then.
+    next_request: Instant,
+}
+
+/// Schedules the retransmission requests of the receiver. A packet is requested as soon as it is declared
+/// lost by the [`ReceiveBuffer`], then again every round-trip time until it arrives, its deadline in the
+/// buffer passes or the maximum number of requests is reached.
+pub struct NackScheduler {
+    max_retries: u32,
+
+    /// Interval between two requests for the same packet.
+    rtt: Duration,
+
+    /// Packets being requested, by extended sequence number.
+    pending: BTreeMap<u64, PendingNack>,
+
+    /// Packets given up on that are still missing in the buffer, they shall not be requested again.
+    given_up: BTreeSet<u64>,
+
+    /// Most recent finished requests, oldest first.
+    finished: VecDeque<(u64, RetryHistory)>,
+}
+
+impl NackScheduler {
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            rtt: DEFAULT_RTT,
+            pending: BTreeMap::new(),
+            given_up: BTreeSet::new(),
+            finished: VecDeque::new(),
+        }
+    }
+
+    /// Updates the interval between two requests for the same packet.
+    pub fn set_rtt(&mut self, rtt: Duration) {
+        self.rtt = rtt;
+    }
+
+    /// A missing packet arrived.
+    pub fn on_received(&mut self, extended_seq: u64, now: Instant) {
+        self.finish(extended_seq, NackOutcome::Recovered(now));
+    }
+
+    /// Extended sequence numbers of the packets to request at `now`.
+    pub fn poll_requests(&mut self, buffer: &ReceiveBuffer, now: Instant) -> Vec<u64> {
+        self.given_up.retain(|seq| buffer.is_missing(*seq));
+        for seq in buffer.lost(now) {
+            if self.given_up.contains(&seq) {
+                continue;
+            }
+            self.pending.entry(seq).or_insert_with(|| PendingNack {
+                history: RetryHistory {
+                    lost_at: now,
+                    requests: vec![],
+                    outcome: None,
+                },
+                next_request: now,
+            });
+        }
+
+        let mut requests = vec![];
+        let mut finished = vec![];
+        for (&seq, nack) in self.pending.iter_mut() {
+            let expired = !buffer.is_missing(seq)
+                || buffer.deadline(seq).is_some_and(|deadline| deadline <= now);
+            if expired {
+                finished.push((seq, NackOutcome::Expired(now)));
+            } else if nack.next_request > now {
+                continue;
+            } else if nack.history.requests.len() >= self.max_retries as usize {
+                // The last request had a round-trip time to be answered.
+                finished.push((seq, NackOutcome::RetriesExhausted(now)));
+            } else {
+                nack.history.requests.push(now);
+                nack.next_request = now + self.rtt;
+                requests.push(seq);
+            }
+        }
+
+        for (seq, outcome) in finished {
+            self.given_up.insert(seq);
+            self.finish(seq, outcome);
+        }
+        requests
+    }
+
+    /// Next instant a packet is due to be requested again.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.pending.values().map(|nack| nack.next_request).min()
+    }
+
+    /// Requests made for a packet, whether it is still requested or not.
+    pub fn history(&self, extended_seq: u64) -> Option<&RetryHistory> {
+        self.pending
+            .get(&extended_seq)
+            .map(|nack| &nack.history)
+            .or_else(|| {
+                self.finished
+                    .iter()
+                    .rev()
+                    .find(|(seq, _)| *seq == extended_seq)
+                    .map(|(_, history)| history)
+            })
+    }
+
+    /// Most recent finished requests, oldest first.
+    pub fn finished(&self) -> impl Iterator<Item = (u64, &RetryHistory)> {
+        self.finished.iter().map(|(seq, history)| (*seq, history))
+    }
+
+    /// Drops every request, to be used when the sender restarts its sequence.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.given_up.clear();
+        self.finished.clear();
+    }
+
+    fn finish(&mut self, extended_seq: u64, outcome: NackOutcome) {
+        let Some(mut nack) = self.pending.remove(&extended_seq) else {
+            return;
+        };
+        nack.history.outcome = Some(outcome);
+        if self.finished.len() == HISTORY_SIZE {
+            self.finished.pop_front();
+        }
+        self.finished.push_back((extended_seq, nack.history));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receive_buffer::{Insertion, PlayoutReference};
+
+    const RTT: Duration = Duration::from_millis(20);
+
+    /// Buffer missing packet 1 since `start`, lost 5 ms later and due 100 ms later.
+    fn buffer(start: Instant) -> ReceiveBuffer {
+        let mut buffer = ReceiveBuffer::new(
+            Duration::from_millis(100),
+            Duration::from_millis(5),
+            PlayoutReference::Arrival,
+            90_000,
+        );
+        buffer.insert(0, 0, vec![0], false, start);
+        buffer.insert(2, 0, vec![2], false, start);
+        buffer
+    }
+
+    fn scheduler(max_retries: u32) -> NackScheduler {
+        let mut scheduler = NackScheduler::new(max_retries);
+        scheduler.set_rtt(RTT);
+        scheduler
+    }
+
+    #[test]
+    fn requests_are_spaced_by_the_rtt() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let buffer = buffer(start);
+        let mut scheduler = scheduler(3);
+
+        // Still in the reorder section.
+        assert_eq!(scheduler.poll_requests(&buffer, at(4)), []);
+        assert_eq!(scheduler.poll_requests(&buffer, at(5)), [1]);
+        assert_eq!(scheduler.poll_timeout(), Some(at(25)));
+        assert_eq!(scheduler.poll_requests(&buffer, at(24)), []);
+        assert_eq!(scheduler.poll_requests(&buffer, at(25)), [1]);
+        assert_eq!(scheduler.poll_requests(&buffer, at(45)), [1]);
+
+        // The last request had a round-trip time to be answered.
+        assert_eq!(scheduler.poll_requests(&buffer, at(65)), []);
+        assert_eq!(
+            scheduler.history(1),
+            Some(&RetryHistory {
+                lost_at: at(5),
+                requests: vec![at(5), at(25), at(45)],
+                outcome: Some(NackOutcome::RetriesExhausted(at(65))),
+            })
+        );
+        assert_eq!(scheduler.poll_timeout(), None);
+
+        // Given up on while still missing.
+        assert_eq!(scheduler.poll_requests(&buffer, at(85)), []);
+    }
+
+    #[test]
+    fn requests_stop_at_the_deadline() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let buffer = buffer(start);
+        let mut scheduler = scheduler(10);
+
+        for ms in [5, 25, 45, 65, 85] {
+            assert_eq!(scheduler.poll_requests(&buffer, at(ms)), [1]);
+        }
+        assert_eq!(buffer.deadline(1), Some(at(100)));
+        assert_eq!(scheduler.poll_requests(&buffer, at(100)), []);
+        assert_eq!(
+            scheduler.history(1).and_then(|history| history.outcome),
+            Some(NackOutcome::Expired(at(100)))
+        );
+        assert_eq!(scheduler.poll_requests(&buffer, at(105)), []);
+    }
+
+    #[test]
+    fn arrival_cancels_the_requests() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut buffer = buffer(start);
+        let mut scheduler = scheduler(3);
+
+        assert_eq!(scheduler.poll_requests(&buffer, at(5)), [1]);
+        assert_eq!(
+            buffer.insert(1, 0, vec![1], true, at(10)),
+            Insertion::Stored { was_missing: true }
+        );
+        scheduler.on_received(1, at(10));
+
+        assert_eq!(scheduler.poll_timeout(), None);
+        assert_eq!(scheduler.poll_requests(&buffer, at(25)), []);
+        assert_eq!(
+            scheduler.finished().collect::<Vec<_>>(),
+            [(
+                1,
+                &RetryHistory {
+                    lost_at: at(5),
+                    requests: vec![at(5)],
+                    outcome: Some(NackOutcome::Recovered(at(10))),
+                }
+            )]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Time reference the constant release delay is applied to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayoutReference {
+    /// Packets are released `buffer_size` after they arrived. Retransmitted packets arrive late by design,
+    /// they are released when the gap they fill was due, `buffer_size` after it was detected.
+    Arrival,
+
+    /// Packets are released `buffer_size` after the instant their RTP timestamp maps to, the mapping being
+    /// anchored on the first packet received. Retransmitted packets keep the spacing of the original stream.
+    RtpTimestamp,
+}
+
+/// Outcome of inserting a packet in the [`ReceiveBuffer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Insertion {
+    /// The packet is stored. `was_missing` is set if it fills a gap.
+    Stored { was_missing: bool },
+
+    /// The packet is already stored.
+    Duplicate,
+
+    /// The packet arrived after its turn to be released, it is dropped.
+    TooLate,
+}
+
+struct BufferedPacket {
+    payload: Vec<u8>,
+    release_at: Instant,
+}
+
+/// Packet not received yet.
+#[derive(Clone, Copy)]
+struct Gap {
+    detected_at: Instant,
+
+    /// Instant the packet would be released at, it is given up on afterwards: the release time of the packet
+    /// that revealed the gap.
+    due_at: Instant,
+}
+
+/// Reorder and latency buffer of the receiver. Packets are placed by extended sequence number, held for the
+/// configured latency, then released to the application in order at a constant delay. Gaps in the sequence
+/// are declared lost only once they stayed open for the reorder section, as packets may just be reordered.
+pub struct ReceiveBuffer {
+    /// Constant delay applied to the packets before they are released.
+    latency: Duration,
+
+    reorder_section: Duration,
+
+    playout_reference: PlayoutReference,
+
+    /// Frequency of the RTP timestamp clock, in Hz.
+    clock_rate: u32,
+
+    /// Arrival time and RTP timestamp of the first packet, for the RTP timestamp playout reference.
+    timestamp_anchor: Option<(Instant, u32)>,
+
+    packets: BTreeMap<u64, BufferedPacket>,
+
+    /// Packets not received yet. The ones still inside the reorder section are not reported as lost yet.
+    missing: BTreeMap<u64, Gap>,
+
+    /// Extended sequence number of the next packet to release.
+    next_seq: Option<u64>,
+
+    /// Highest extended sequence number stored.
+    highest_seq: Option<u64>,
+}
+
+impl ReceiveBuffer {
+    pub fn new(
+        latency: Duration,
+        reorder_section: Duration,
+        playout_reference: PlayoutReference,
+        clock_rate: u32,
+    ) -> Self {
+        Self {
+            latency,
+            reorder_section,
+            playout_reference,
+            clock_rate,
+            timestamp_anchor: None,
+            packets: BTreeMap::new(),
+            missing: BTreeMap::new(),
+            next_seq: None,
+            highest_seq: None,
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        extended_seq: u64,
+        rtp_timestamp: u32,
+        payload: Vec<u8>,
+        is_retransmission: bool,
+        now: Instant,
+    ) -> Insertion {
+        if self
+            .next_seq
+            .is_some_and(|next_seq| extended_seq < next_seq)
+        {
+            return Insertion::TooLate;
+        }
+        if self.packets.contains_key(&extended_seq) {
+            return Insertion::Duplicate;
+        }
+
+        let gap = self.missing.remove(&extended_seq);
+        let release_at = match (self.playout_reference, gap) {
+            // Released in the slot it fills, so never before the packets preceding it.
+            (PlayoutReference::Arrival, Some(gap)) if is_retransmission => gap.due_at,
+            _ => self.release_time(rtp_timestamp, now),
+        };
+
+        match self.highest_seq {
+            Some(highest_seq) if extended_seq > highest_seq + 1 => {
+                for seq in highest_seq + 1..extended_seq {
+                    self.missing.insert(
+                        seq,
+                        Gap {
+                            detected_at: now,
+                            due_at: release_at,
+                        },
+                    );
+                }
+            }
+            _ => {}
+        }
+        self.highest_seq = self.highest_seq.max(Some(extended_seq));
+        self.next_seq.get_or_insert(extended_seq);
+
+        self.packets.insert(
+            extended_seq,
+            BufferedPacket {
+                payload,
+                release_at,
+            },
+        );
+
+        Insertion::Stored {
+            was_missing: gap.is_some(),
+        }
+    }
+
+    fn release_time(&mut self, rtp_timestamp: u32, now: Instant) -> Instant {
+        match self.playout_reference {
+            PlayoutReference::Arrival => now + self.latency,
+            PlayoutReference::RtpTimestamp => {
+                let (anchor, anchor_timestamp) =
+                    *self.timestamp_anchor.get_or_insert((now, rtp_timestamp));
+                let ticks = rtp_timestamp.wrapping_sub(anchor_timestamp) as i32;
+                let offset = Duration::from_nanos(
+                    ticks.unsigned_abs() as u64 * 1_000_000_000 / self.clock_rate as u64,
+                );
+                let sampled_at = if ticks >= 0 {
+                    anchor + offset
+                } else {
+                    anchor.checked_sub(offset).unwrap_or(anchor)
+                };
+                sampled_at + self.latency
+            }
+        }
+    }
+
+    /// Releases the next packet in order if its time has come. Missing packets are skipped once they are due,
+    /// they are then lost for good.
+    pub fn pop(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let (&seq, packet) = self.packets.first_key_value()?;
+        while let Some(entry) = self.missing.first_entry() {
+            if *entry.key() > seq {
+                break;
+            }
+            if entry.get().due_at > now {
+                return None;
+            }
+            entry.remove();
+        }
+        if packet.release_at > now {
+            return None;
+        }
+
+        self.next_seq = Some(seq + 1);
+        self.packets.remove(&seq).map(|packet| packet.payload)
+    }
+
+    /// Packets missing for longer than the reorder section, which can be requested for retransmission.
+    pub fn lost(&self, now: Instant) -> impl Iterator<Item = u64> + '_ {
+        self.missing
+            .iter()
+            .filter(move |(_, gap)| {
+                now.saturating_duration_since(gap.detected_at) >= self.reorder_section
+            })
+            .map(|(seq, _)| *seq)
+    }
+
+    /// Whether the packet is missing and may still be released if it arrives.
+    pub fn is_missing(&self, extended_seq: u64) -> bool {
+        self.missing.contains_key(&extended_seq)
+    }
+
+    /// Whether the packet lies between the next packet to release and the highest one stored, where a
+    /// retransmission can still be placed.
+    pub fn in_window(&self, extended_seq: u64) -> bool {
+        match (self.next_seq, self.highest_seq) {
+            (Some(next_seq), Some(highest_seq)) => (next_seq..=highest_seq).contains(&extended_seq),
+            _ => false,
+        }
+    }
+
+    /// Instant a missing packet is given up on, when it was due for release.
+    pub fn deadline(&self, extended_seq: u64) -> Option<Instant> {
+        self.missing.get(&extended_seq).map(|gap| gap.due_at)
+    }
+
+    /// Next instant the buffer needs attention: a packet to release or a gap leaving the reorder section.
+    pub fn poll_timeout(&self, now: Instant) -> Option<Instant> {
+        // A gap before the next packet holds it back until the gap is due.
+        let release = match (
+            self.packets.first_key_value(),
+            self.missing.first_key_value(),
+        ) {
+            (Some((seq, _)), Some((missing_seq, gap))) if missing_seq < seq => Some(gap.due_at),
+            (Some((_, packet)), _) => Some(packet.release_at),
+            (None, _) => None,
+        };
+        let reorder = self
+            .missing
+            .values()
+            .map(|gap| gap.detected_at + self.reorder_section)
+            .filter(|reorder_end| *reorder_end > now)
+            .min();
+        release.into_iter().chain(reorder).min()
+    }
+
+    /// Drops everything, to be used when the sender restarts its sequence.
+    pub fn reset(&mut self) {
+        self.timestamp_anchor = None;
+        self.packets.clear();
+        self.missing.clear();
+        self.next_seq = None;
+        self.highest_seq = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATENCY: Duration = Duration::from_millis(100);
+
+    fn buffer() -> ReceiveBuffer {
+        ReceiveBuffer::new(
+            LATENCY,
+            Duration::from_millis(5),
+            PlayoutReference::Arrival,
+            90_000,
+        )
+    }
+
+    fn insert(buffer: &mut ReceiveBuffer, seq: u64, is_retransmission: bool, now: Instant) {
+        assert_eq!(
+            buffer.insert(seq, 0, vec![seq as u8], is_retransmission, now),
+            Insertion::Stored {
+                was_missing: is_retransmission
+            }
+        );
+    }
+
+    fn pop_all(buffer: &mut ReceiveBuffer, now: Instant) -> Vec<u8> {
+        std::iter::from_fn(|| buffer.pop(now)).flatten().collect()
+    }
+
+    #[test]
+    fn retransmission_waits_for_the_gaps_before_it() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut buffer = buffer();
+
+        insert(&mut buffer, 0, false, at(0));
+        // 1 and 2 are missing, due when 3 is.
+        insert(&mut buffer, 3, false, at(30));
+        assert_eq!(buffer.deadline(1), Some(at(30) + LATENCY));
+        assert_eq!(pop_all(&mut buffer, at(100)), [0]);
+
+        // 2 comes back while 1 is still missing, it must not be released before 1 is due.
+        insert(&mut buffer, 2, true, at(105));
+        assert_eq!(pop_all(&mut buffer, at(105)), []);
+        assert_eq!(buffer.poll_timeout(at(105)), Some(at(130)));
+
+        insert(&mut buffer, 1, true, at(120));
+        assert_eq!(pop_all(&mut buffer, at(129)), []);
+        assert_eq!(pop_all(&mut buffer, at(130)), [1, 2, 3]);
+    }
+
+    #[test]
+    fn gap_is_skipped_once_due() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut buffer = buffer();
+
+        insert(&mut buffer, 0, false, at(0));
+        insert(&mut buffer, 3, false, at(30));
+        assert_eq!(pop_all(&mut buffer, at(100)), [0]);
+        insert(&mut buffer, 2, true, at(105));
+
+        assert!(buffer.is_missing(1));
+        assert_eq!(pop_all(&mut buffer, at(130)), [2, 3]);
+        assert!(!buffer.is_missing(1));
+        assert_eq!(
+            buffer.insert(1, 0, vec![1], true, at(131)),
+            Insertion::TooLate
+        );
+    }
+}
@@ -1,24 +1,637 @@
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
 
-use crate::common::RistListenerPort;
+use risty_core::{Marshal, NtpClock, Unmarshal};
+use risty_proto::rtp::packet::RtpPacket;
+use risty_proto::rtp::rtcp::compound::{CompoundRtcp, Feedback, Report, SubPackets};
+use risty_proto::rtp::rtcp::feedback::encoder::{NackEncoder, NackFormats};
+use risty_proto::rtp::rtcp::packet::RtcpPacket;
+use risty_proto::rtp::rtcp::receiver_report::ReceiverReport;
+use risty_proto::rtp::rtcp::report_block::ReportBlock;
+use risty_proto::rtp::rtcp::sdes::{Chunk, Sdes};
 
-struct Receiver {
+use crate::common::{RistListenerPort, MAX_DATAGRAM_SIZE};
+use crate::error::{ConfigError, RistError};
+use crate::jitter::JitterEstimator;
+use crate::loss::LossAccounting;
+use crate::multicast;
+use crate::nack_scheduler::{NackScheduler, RetryHistory};
+use crate::receive_buffer::{Insertion, PlayoutReference, ReceiveBuffer};
+use crate::rtcp;
+use crate::rtt::RttEstimator;
+use crate::sequence::{SequenceTracker, SequenceUpdate};
+use crate::session::{self, Session, SessionEvent, SessionState};
+use crate::stats::ReceiverStats;
+
+pub struct Receiver {
     listen_port: RistListenerPort, // P
     /// RIST receivers shall listen on UDP port P+1 for RTCP packets from the sender. The source
     /// IP address of such packets is denoted by S and their source UDP port is denoted by R’.
-    sender_address: String, // S
-    sender_rtcp_port: u16,         // R': obtained from
+    sender_address: Option<IpAddr>, // S
+    sender_rtcp_port: u16,         // R': obtained from the first valid RTCP packet received on P+1
+
+    /// SSRC of the SR packets coming from S, a packet from another address carrying it is the sender moving
+    /// behind a NAT.
+    sender_ssrc: Option<u32>,
+
+    config: Config,
+
+    /// SSRC of the RTCP packets sent by the receiver.
+    ssrc: u32,
+
+    /// SSRC of the original packets of the media source, with LSB=0.
+    media_ssrc: Option<u32>,
 
-    use_upnp: bool, // TODO
+    sequence: SequenceTracker,
+    jitter: JitterEstimator,
+    loss: LossAccounting,
+    buffer: ReceiveBuffer,
+    nacks: NackScheduler,
+    rtt: RttEstimator,
+    scheduler: rtcp::Scheduler,
+    session: Session,
+
+    rtcp_transmits: VecDeque<Vec<u8>>,
 }
 
-struct Config {
-    buffer_size: Duration,
-    reorder_section: Duration,
-    max_number_of_retry_per_packet: u32,
+impl Receiver {
+    pub fn new(
+        listen_port: RistListenerPort,
+        config: Config,
+        now: Instant,
+    ) -> Result<Self, RistError> {
+        config.validate()?;
+
+        Ok(Self {
+            listen_port,
+            sender_address: config.sender_address,
+            sender_rtcp_port: 0,
+            sender_ssrc: None,
+            ssrc: rand::random(),
+            media_ssrc: None,
+            // RIST flows are point to point, there is no need to wait for a few packets before trusting the source.
+            sequence: SequenceTracker::new(0),
+            jitter: JitterEstimator::new(config.clock_rate),
+            loss: LossAccounting::new(),
+            buffer: ReceiveBuffer::new(
+                config.buffer_size,
+                config.reorder_section,
+                config.playout_reference,
+                config.clock_rate,
+            ),
+            nacks: NackScheduler::new(config.max_number_of_retry_per_packet),
+            rtt: RttEstimator::new(NtpClock::new(now)),
+            scheduler: rtcp::Scheduler::new(config.rtcp.interval),
+            // Multicast receivers only listen to the sender, the session is theirs alone.
+            session: Session::new(
+                session::Config {
+                    stale_timeout: config.rtcp.session.stale_timeout,
+                    peer_timeout: config.rtcp.session.peer_timeout,
+                },
+                false,
+            ),
+            rtcp_transmits: VecDeque::new(),
+            config,
+        })
+    }
+
+    /// this function shall be called when receiving a packet on the rtp socket
+    /// - Returns the extended sequence number of the packet, or `None` if the packet was dropped.
+    /// - `source` is the address the packet was received from. Once the sender address is pinned or learnt
+    ///   from its RTCP packets, packets from other addresses are rejected with `RistError::UnexpectedSource`.
+    pub fn handle_rtp_input(
+        &mut self,
+        packet: &[u8],
+        source: SocketAddr,
+        now: Instant,
+    ) -> Result<Option<u64>, RistError> {
+        if self
+            .sender_address
+            .is_some_and(|address| address != source.ip())
+        {
+            return Err(RistError::UnexpectedSource(source));
+        }
+        let packet = RtpPacket::unmarshal(packet)?;
+        let is_retransmission = packet.header.ssrc & 1 == 1;
+        self.media_ssrc = Some(packet.header.ssrc & !1);
+
+        let extended_seq = if is_retransmission {
+            // Retransmissions arrive far behind the highest sequence number on purpose, the tracker would take
+            // them for a jump and restart the sequence. They are only placed relative to it.
+            if !self.sequence.is_valid() {
+                return Ok(None);
+            }
+            let extended_seq = self.sequence.extend(packet.header.sequence_number);
+            if !self.buffer.in_window(extended_seq) {
+                return Ok(None);
+            }
+            extended_seq
+        } else {
+            match self.sequence.update(packet.header.sequence_number) {
+                SequenceUpdate::Valid(extended_seq) => extended_seq,
+                SequenceUpdate::Restarted(extended_seq) => {
+                    self.jitter.reset();
+                    self.loss.reset();
+                    self.buffer.reset();
+                    self.nacks.reset();
+                    extended_seq
+                }
+                SequenceUpdate::Probation | SequenceUpdate::Invalid => return Ok(None),
+            }
+        };
+
+        let insertion = self.buffer.insert(
+            extended_seq,
+            packet.header.timestamp,
+            packet.payload.to_vec(),
+            is_retransmission,
+            now,
+        );
+        let was_missing = insertion == (Insertion::Stored { was_missing: true });
+        if was_missing {
+            // Without echoes, the retransmissions are the only way for the receiver to measure the RTT.
+            if is_retransmission && !self.config.rtcp.enable_rtt_echoes {
+                if let Some(history) = self.nacks.history(extended_seq) {
+                    self.rtt.on_retransmission(&history.requests, now);
+                    if let Some(rtt) = self.rtt.smoothed() {
+                        self.nacks.set_rtt(rtt);
+                    }
+                }
+            }
+            self.nacks.on_received(extended_seq, now);
+        }
+        if is_retransmission {
+            // Only retransmissions filling a gap in time recover a packet.
+            if was_missing {
+                self.loss.record_recovered();
+            }
+        } else {
+            if insertion != Insertion::Duplicate {
+                self.loss.record_original();
+            }
+            // Retransmitted packets are sent late on purpose, they would skew the jitter estimate.
+            self.jitter.update(packet.header.timestamp, now);
+        }
+
+        Ok(Some(extended_seq))
+    }
+
+    /// this function shall be called when receiving a packet on the rtcp socket
+    /// - `source` is the address the packet was received from. The first valid packet sets the address the RTCP
+    ///   packets are sent back to, S and R'. Afterwards, they only change once the session went stale, for a
+    ///   packet carrying the SSRC of the sender, as when a NAT rebinds the flow. Packets from other sources are
+    ///   rejected with `RistError::UnexpectedSource`. A pinned sender address never changes, only the port is
+    ///   learnt. Once the session is closed, the packets are ignored.
+    pub fn handle_rtcp_input(
+        &mut self,
+        packet: &[u8],
+        source: SocketAddr,
+        now: Instant,
+    ) -> Result<(), RistError> {
+        // Validates the ordering of the compound packet before looking at its content.
+        CompoundRtcp::unmarshal(packet)?;
+        let packets = SubPackets::new(packet)
+            .map(|sub_packet| sub_packet.and_then(|(_, bytes)| RtcpPacket::unmarshal(bytes)))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Senders start their compound packets with a SR. Packets starting with a RR come from the other
+        // receivers of a multicast group, or from this one through the loopback.
+        let ssrc = match packets.first() {
+            Some(RtcpPacket::SenderReport(sr)) => sr.ssrc_sender,
+            _ => return Ok(()),
+        };
+        if self.session.state() == SessionState::Closed {
+            return Ok(());
+        }
+        self.learn_sender(source, ssrc)?;
+        self.session.on_rtcp(source, &packets, now);
+
+        for packet in packets {
+            match packet {
+                RtcpPacket::SenderReport(sr) => {
+                    self.rtt.on_sender_report(sr.sender_info.ntp_time(), now);
+                }
+                RtcpPacket::RttEchoResponse(response) => {
+                    self.rtt.on_echo_response(&response, now);
+                    if let Some(rtt) = self.rtt.smoothed() {
+                        self.nacks.set_rtt(rtt);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets S and R' from a valid compound packet of the sender. Once known, they only change when the packet
+    /// carries the SSRC of the sender and the session went stale, which happens when a NAT rebinds the flow to a
+    /// new address or port. The SSRC is sent in clear, a forged packet would otherwise take over the feedback
+    /// path while the sender is still there.
+    ///
+    /// A pinned sender address never changes, only the port is learnt.
+    fn learn_sender(&mut self, source: SocketAddr, ssrc: u32) -> Result<(), RistError> {
+        if let Some(pinned) = self.config.sender_address {
+            if pinned != source.ip() {
+                return Err(RistError::UnexpectedSource(source));
+            }
+        } else if let Some(destination) = self.sender_rtcp_address() {
+            let rebinds =
+                self.sender_ssrc == Some(ssrc) && self.session.state() == SessionState::Stale;
+            if destination != source && !rebinds {
+                return Err(RistError::UnexpectedSource(source));
+            }
+        }
+
+        self.sender_address = Some(source.ip());
+        self.sender_rtcp_port = source.port();
+        self.sender_ssrc = Some(ssrc);
+        Ok(())
+    }
+
+    /// S and R', once the first RTCP packet of the sender is received.
+    pub fn sender_rtcp_address(&self) -> Option<SocketAddr> {
+        let address = self.sender_address?;
+        (self.sender_rtcp_port != 0).then_some(SocketAddr::new(address, self.sender_rtcp_port))
+    }
+
+    /// Destination of the RTCP packets: S and R' in unicast, the group on port P+1 when receiving from a
+    /// multicast group. `None` until the sender is known.
+    pub fn rtcp_destination(&self) -> Option<SocketAddr> {
+        match &self.config.multicast {
+            Some(membership) => Some(SocketAddr::new(
+                membership.group,
+                self.listen_port.get() + 1,
+            )),
+            None => self.sender_rtcp_address(),
+        }
+    }
+
+    /// Round-trip time to the sender.
+    pub fn rtt(&self) -> &RttEstimator {
+        &self.rtt
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            multicast: self.config.multicast.is_some(),
+            rtt_echo: self.config.rtcp.enable_rtt_echoes,
+        }
+    }
+
+    /// Liveness and identity of the sender.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Closes the session, the RTCP packets of the sender are ignored from now on.
+    pub fn close(&mut self, now: Instant) {
+        self.session.close(now);
+    }
+
+    /// Next session event, the session timeouts are checked by [`Receiver::poll_rtcp_transmit`].
+    pub fn poll_event(&mut self) -> Option<SessionEvent> {
+        self.session.poll_event()
+    }
+
+    /// Next payload to hand to the application, in sequence order and once the buffer latency elapsed.
+    pub fn poll_payload(&mut self, now: Instant) -> Option<Vec<u8>> {
+        self.buffer.pop(now)
+    }
+
+    /// this function shall be called to send a rtcp packet to the sender
+    /// - Returns the next compound packet to send to [`Receiver::rtcp_destination`].
+    ///   NACKs are sent as soon as packets are declared lost, the reports at the RTCP interval.
+    pub fn poll_rtcp_transmit(&mut self, now: Instant) -> Result<Option<Vec<u8>>, RistError> {
+        self.session.handle_timeout(now);
+        if self.rtcp_transmits.is_empty() {
+            self.queue_rtcp(now)?;
+        }
+        Ok(self.rtcp_transmits.pop_front())
+    }
+
+    fn queue_rtcp(&mut self, now: Instant) -> Result<(), RistError> {
+        let sdes = Sdes::new(Chunk::new(self.ssrc, self.config.cname.clone()));
+
+        if let Some(media_ssrc) = self.media_ssrc {
+            let lost = self
+                .nacks
+                .poll_requests(&self.buffer, now)
+                .into_iter()
+                .map(|extended_seq| extended_seq as u16)
+                .collect::<Vec<_>>();
+            // The report block is kept for the periodic reports, so that the fraction lost covers a whole
+            // report interval.
+            let rr = ReceiverReport::new_empty(self.ssrc);
+            let max_nack_size = MAX_DATAGRAM_SIZE - rr.marshal_size() - sdes.marshal_size();
+            for nack in
+                NackEncoder::new(self.config.nack_formats, max_nack_size).encode(media_ssrc, &lost)
+            {
+                let compound = CompoundRtcp::builder(Report::Receiver(&rr), &sdes)
+                    .push(nack.as_feedback())
+                    .build()?;
+                self.rtcp_transmits.push_back(compound.as_bytes().to_vec());
+            }
+        }
+
+        if self.scheduler.poll_report(now) {
+            let rr = match self.report_block(now) {
+                Some(block) => ReceiverReport::new_with_report_block(self.ssrc, block),
+                None => ReceiverReport::new_empty(self.ssrc),
+            };
+            let echo = self
+                .media_ssrc
+                .filter(|_| self.config.rtcp.enable_rtt_echoes)
+                .map(|media_ssrc| self.rtt.echo_request(media_ssrc, now));
+
+            let mut builder = CompoundRtcp::builder(Report::Receiver(&rr), &sdes);
+            if let Some(echo) = &echo {
+                builder = builder.push(Feedback::RttEcho(echo));
+            }
+            self.rtcp_transmits
+                .push_back(builder.build()?.as_bytes().to_vec());
+        }
+
+        Ok(())
+    }
+
+    /// Retransmission requests made for a packet, for debugging.
+    pub fn retry_history(&self, extended_seq: u64) -> Option<&RetryHistory> {
+        self.nacks.history(extended_seq)
+    }
+
+    /// Instant the state machine shall be polled again at, even if nothing was received.
+    pub fn poll_timeout(&self, now: Instant) -> Option<Instant> {
+        self.buffer
+            .poll_timeout(now)
+            .into_iter()
+            .chain(self.nacks.poll_timeout())
+            .chain(self.scheduler.poll_timeout())
+            .chain(self.session.poll_timeout())
+            .min()
+    }
+
+    /// The highest sequence number received, extended with the count of sequence number cycles.
+    pub fn highest_extended_seq_num_received(&self) -> u32 {
+        self.sequence.extended_max()
+    }
+
+    pub fn stats(&self) -> ReceiverStats {
+        let expected = self.sequence.expected();
+        ReceiverStats {
+            packets_received: self.sequence.received(),
+            highest_extended_seq_num: self.sequence.extended_max(),
+            jitter: self.jitter.jitter(),
+            jitter_duration: self.jitter.jitter_duration(),
+            packets_lost_before_retransmission: self.loss.lost_before_retransmission(expected),
+            packets_recovered: self.loss.recovered(),
+            packets_lost: self.loss.lost_after_retransmission(expected),
+        }
+    }
+
+    /// Report block about the media source, to be sent in RR packets. `None` until a valid packet is received.
+    /// Each call starts a new report interval for the fraction of packets lost.
+    pub fn report_block(&mut self, now: Instant) -> Option<ReportBlock> {
+        let ssrc = self.media_ssrc.filter(|_| self.sequence.is_valid())?;
+        let loss = self.loss.report(self.sequence.expected());
+        let (last_sr_timestamp, delay_since_last_sr) = self.rtt.last_sr(now);
+        Some(ReportBlock {
+            ssrc,
+            fraction_lost: loss.fraction_lost,
+            cumm_packets_lost: loss.cumm_packets_lost,
+            highest_extended_seq_num_received: self.sequence.extended_max(),
+            interarrival_jitter: self.jitter.jitter(),
+            last_sr_timestamp,
+            delay_since_last_sr,
+        })
+    }
+}
+
+pub struct Config {
+    /// Constant delay applied to the packets before they are handed to the application, it bounds the time
+    /// available to recover lost packets.
+    pub buffer_size: Duration,
+
+    /// How long a gap in the sequence is waited for before the missing packets are considered lost.
+    pub reorder_section: Duration,
+
+    /// Time reference the buffer size is applied to.
+    pub playout_reference: PlayoutReference,
+
+    /// Maximum number of retransmission requests for a lost packet.
+    pub max_number_of_retry_per_packet: u32,
+
+    /// Frequency of the RTP timestamp clock in Hz, `MPEG_TS_CLOCK_RATE` for MPEG-2 TS payloads.
+    pub clock_rate: u32,
+
+    /// Canonical name carried in the SDES packets, typically "user@host".
+    pub cname: String,
+
+    pub rtcp: rtcp::Config,
+
+    /// NACK formats the sender supports.
+    pub nack_formats: NackFormats,
+
+    /// Multicast group to receive from, `None` to receive unicast.
+    pub multicast: Option<multicast::Membership>,
+
+    /// Pins the sender to this address, packets from other addresses are dropped. `None` accepts the sender the
+    /// first RTCP packet comes from.
+    pub sender_address: Option<IpAddr>,
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.buffer_size.is_zero() {
+            return Err(ConfigError::Zero {
+                name: "buffer size",
+            });
+        }
+        if self.reorder_section >= self.buffer_size {
+            return Err(ConfigError::ReorderSectionTooLong {
+                reorder_section: self.reorder_section,
+                buffer_size: self.buffer_size,
+            });
+        }
+        if self.clock_rate == 0 {
+            return Err(ConfigError::Zero { name: "clock rate" });
+        }
+        rtcp::validate_cname(&self.cname)?;
+        if let Some(membership) = &self.multicast {
+            membership.validate()?;
+        }
+        self.rtcp.validate()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The receiver joined a multicast group.
+    pub multicast: bool,
+
+    /// The receiver measures the RTT with echo requests.
+    pub rtt_echo: bool,
 }
 
-struct Capabilities {
-    multicast: bool,
-    rtt_echo: bool,
+#[cfg(test)]
+mod tests {
+    use risty_proto::rtp::rtcp::sender_report::SenderReport;
+
+    use super::*;
+
+    const SSRC: u32 = 0x1234_5678;
+
+    fn config() -> Config {
+        Config {
+            buffer_size: Duration::from_millis(100),
+            reorder_section: Duration::from_millis(5),
+            playout_reference: PlayoutReference::Arrival,
+            max_number_of_retry_per_packet: 3,
+            clock_rate: 90_000,
+            cname: "receiver@test".to_string(),
+            rtcp: rtcp::Config {
+                interval: Duration::from_millis(100),
+                enable_rtt_echoes: true,
+                session: session::Config {
+                    stale_timeout: Duration::from_millis(300),
+                    peer_timeout: Duration::from_millis(800),
+                },
+            },
+            nack_formats: NackFormats::Any,
+            multicast: None,
+            sender_address: None,
+        }
+    }
+
+    fn rtp(sequence_number: u16, ssrc: u32) -> Vec<u8> {
+        let payload = sequence_number.to_be_bytes();
+        let packet = RtpPacket::new(33, sequence_number, 0, ssrc, &payload);
+        let mut bytes = vec![0; packet.marshal_size()];
+        packet.marshal(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn sender_report(ssrc: u32) -> Vec<u8> {
+        let sr = SenderReport::new(ssrc);
+        let sdes = Sdes::new(Chunk::new(ssrc, "sender@test".to_string()));
+        CompoundRtcp::builder(Report::Sender(&sr), &sdes)
+            .build()
+            .unwrap()
+            .as_bytes()
+            .to_vec()
+    }
+
+    #[test]
+    fn sender_moves_only_once_the_session_is_stale() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let sender: SocketAddr = "10.0.0.1:5001".parse().unwrap();
+        let rebound: SocketAddr = "10.0.0.2:7001".parse().unwrap();
+        let mut receiver =
+            Receiver::new(RistListenerPort::new(6000).unwrap(), config(), start).unwrap();
+
+        receiver
+            .handle_rtcp_input(&sender_report(SSRC), sender, at(0))
+            .unwrap();
+        assert_eq!(receiver.sender_rtcp_address(), Some(sender));
+
+        // A packet carrying the SSRC of the sender from elsewhere, while the sender is alive.
+        assert!(matches!(
+            receiver.handle_rtcp_input(&sender_report(SSRC), rebound, at(100)),
+            Err(RistError::UnexpectedSource(source)) if source == rebound
+        ));
+        assert!(matches!(
+            receiver.handle_rtp_input(&rtp(0, SSRC), rebound, at(100)),
+            Err(RistError::UnexpectedSource(source)) if source == rebound
+        ));
+
+        receiver.poll_rtcp_transmit(at(300)).unwrap();
+        assert_eq!(receiver.session().state(), SessionState::Stale);
+        assert!(matches!(
+            receiver.handle_rtcp_input(&sender_report(SSRC + 2), rebound, at(300)),
+            Err(RistError::UnexpectedSource(source)) if source == rebound
+        ));
+        receiver
+            .handle_rtcp_input(&sender_report(SSRC), rebound, at(300))
+            .unwrap();
+        assert_eq!(receiver.sender_rtcp_address(), Some(rebound));
+        assert!(matches!(
+            receiver.handle_rtp_input(&rtp(0, SSRC), sender, at(300)),
+            Err(RistError::UnexpectedSource(source)) if source == sender
+        ));
+        assert_eq!(
+            receiver
+                .handle_rtp_input(&rtp(0, SSRC), rebound, at(300))
+                .unwrap(),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn late_retransmissions_do_not_restart_the_sequence() {
+        let now = Instant::now();
+        let source = "127.0.0.1:5000".parse().unwrap();
+        let mut receiver =
+            Receiver::new(RistListenerPort::new(6000).unwrap(), config(), now).unwrap();
+
+        for seq in (0..10).chain(15..400) {
+            assert_eq!(
+                receiver
+                    .handle_rtp_input(&rtp(seq, SSRC), source, now)
+                    .unwrap(),
+                Some(seq as u64)
+            );
+        }
+        // More than 100 sequence numbers behind the highest one, the tracker would see a jump.
+        for seq in 10..13 {
+            assert_eq!(
+                receiver
+                    .handle_rtp_input(&rtp(seq, SSRC | 1), source, now)
+                    .unwrap(),
+                Some(seq as u64)
+            );
+        }
+        // Outside of the buffer window.
+        assert_eq!(
+            receiver
+                .handle_rtp_input(&rtp(500, SSRC | 1), source, now)
+                .unwrap(),
+            None
+        );
+
+        assert_eq!(receiver.stats().packets_received, 395);
+        assert_eq!(receiver.stats().packets_recovered, 3);
+        let released = std::iter::from_fn(|| receiver.poll_payload(now + Duration::from_secs(1)))
+            .map(|payload| u16::from_be_bytes([payload[0], payload[1]]))
+            .collect::<Vec<_>>();
+        assert_eq!(released, (0..13).chain(15..400).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rtt_is_measured_with_retransmissions_without_echoes() {
+        let start = Instant::now();
+        let source = "127.0.0.1:5000".parse().unwrap();
+        let mut config = config();
+        config.rtcp.enable_rtt_echoes = false;
+        let mut receiver =
+            Receiver::new(RistListenerPort::new(6000).unwrap(), config, start).unwrap();
+
+        for seq in [0, 1, 3] {
+            receiver
+                .handle_rtp_input(&rtp(seq, SSRC), source, start)
+                .unwrap();
+        }
+        // 2 leaves the reorder section and is requested.
+        let requested_at = start + Duration::from_millis(10);
+        while receiver.poll_rtcp_transmit(requested_at).unwrap().is_some() {}
+        assert_eq!(receiver.retry_history(2).unwrap().requests, [requested_at]);
+
+        receiver
+            .handle_rtp_input(
+                &rtp(2, SSRC | 1),
+                source,
+                requested_at + Duration::from_millis(30),
+            )
+            .unwrap();
+        assert_eq!(receiver.rtt().smoothed(), Some(Duration::from_millis(30)));
+    }
 }
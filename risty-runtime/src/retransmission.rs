@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Offset of the SSRC in the RTP header.
+const SSRC_OFFSET: usize = 8;
+
+/// Outcome of a retransmission request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lookup {
+    /// The packet to send again, with the retransmission SSRC (LSB=1) and its original sequence number.
+    Found(Vec<u8>),
+
+    /// The packet was sent, but it is older than the buffer size and was dropped.
+    Expired,
+
+    /// The packet was never sent.
+    Unknown,
+}
+
+/// Counters of the retransmission requests received by the sender.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetransmissionStats {
+    /// Packets requested by the receiver.
+    pub requested: u64,
+
+    /// Packets sent again.
+    pub retransmitted: u64,
+
+    /// Packets requested after they aged out of the buffer.
+    pub expired: u64,
+}
+
+struct StoredPacket {
+    sent_at: Instant,
+    bytes: Vec<u8>,
+}
+
+/// Keeps the RTP packets sent for the configured buffer size, to answer the NACKs of the receiver. Packets
+/// are stored in sending order, which is also sequence number order, so the buffer is a ring indexed by the
+/// distance between a sequence number and the oldest one stored.
+pub struct RetransmissionBuffer {
+    retention: Duration,
+
+    /// Sequence number of the oldest packet stored.
+    first_seq: u16,
+
+    packets: VecDeque<StoredPacket>,
+
+    stats: RetransmissionStats,
+}
+
+impl RetransmissionBuffer {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            retention,
+            first_seq: 0,
+            packets: VecDeque::new(),
+            stats: RetransmissionStats::default(),
+        }
+    }
+
+    /// Stores a packet that was just sent. Sequence numbers shall be consecutive.
+    pub fn insert(&mut self, seq: u16, bytes: Vec<u8>, now: Instant) {
+        self.expire(now);
+        if self.packets.is_empty() {
+            self.first_seq = seq;
+        }
+        // The sequence numbers wrap around after 65536 packets, older packets would become ambiguous.
+        if self.packets.len() > u16::MAX as usize {
+            self.packets.pop_front();
+            self.first_seq = self.first_seq.wrapping_add(1);
+        }
+        self.packets.push_back(StoredPacket {
+            sent_at: now,
+            bytes,
+        });
+    }
+
+    /// Looks up a packet requested by the receiver.
+    pub fn lookup(&mut self, seq: u16, now: Instant) -> Lookup {
+        self.expire(now);
+        self.stats.requested += 1;
+
+        let index = seq.wrapping_sub(self.first_seq) as usize;
+        if let Some(packet) = self.packets.get(index) {
+            self.stats.retransmitted += 1;
+            let mut bytes = packet.bytes.clone();
+            bytes[SSRC_OFFSET + 3] |= 1;
+            return Lookup::Found(bytes);
+        }
+
+        // Sequence numbers just before the oldest packet stored were sent and dropped, the ones after the
+        // newest packet were never sent. A distance greater than half the sequence number space means the
+        // sequence number is behind.
+        if index > u16::MAX as usize / 2 {
+            self.stats.expired += 1;
+            Lookup::Expired
+        } else {
+            Lookup::Unknown
+        }
+    }
+
+    pub fn stats(&self) -> RetransmissionStats {
+        self.stats
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(packet) = self.packets.front() {
+            if now.saturating_duration_since(packet.sent_at) <= self.retention {
+                break;
+            }
+            self.packets.pop_front();
+            self.first_seq = self.first_seq.wrapping_add(1);
+        }
+    }
+}
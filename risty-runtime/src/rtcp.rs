@@ -1,6 +1,78 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-struct Config {
-    interval: Duration, // 100ms or less
-    enable_rtt_echoes: bool,
+use rand::Rng;
+
+use crate::error::ConfigError;
+use crate::session;
+
+/// Longest RTCP interval allowed by RIST.
+const MAX_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest CNAME an SDES item can carry.
+pub(crate) const MAX_CNAME_LEN: usize = 255;
+
+pub struct Config {
+    pub interval: Duration, // 100ms or less
+    pub enable_rtt_echoes: bool,
+
+    /// Timeouts of the session, based on the RTCP packets received from the peer.
+    pub session: session::Config,
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.interval.is_zero() {
+            return Err(ConfigError::Zero {
+                name: "RTCP interval",
+            });
+        }
+        if self.interval > MAX_INTERVAL {
+            return Err(ConfigError::RtcpIntervalTooLong(self.interval));
+        }
+        self.session.validate(self.interval)
+    }
+}
+
+pub(crate) fn validate_cname(cname: &str) -> Result<(), ConfigError> {
+    if cname.len() > MAX_CNAME_LEN {
+        return Err(ConfigError::CnameTooLong(cname.len()));
+    }
+    Ok(())
+}
+
+/// Timer of the periodic compound RTCP packets. Each interval is drawn uniformly between 3/4 of the
+/// configured interval and the interval itself, so that flows started together do not stay synchronized
+/// (RFC 3550 section 6.3.1), without ever exceeding the maximum interval mandated by RIST.
+pub struct Scheduler {
+    interval: Duration,
+
+    /// Instant the next report is due, `None` until the first poll.
+    next_report: Option<Instant>,
+}
+
+impl Scheduler {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next_report: None,
+        }
+    }
+
+    /// Whether a report is due at `now`. If it is, the next one is scheduled. The first poll is always due.
+    pub fn poll_report(&mut self, now: Instant) -> bool {
+        if self
+            .next_report
+            .is_some_and(|next_report| now < next_report)
+        {
+            return false;
+        }
+        let interval = rand::thread_rng().gen_range(self.interval * 3 / 4..=self.interval);
+        self.next_report = Some(now + interval);
+        true
+    }
+
+    /// Instant the next report is due.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.next_report
+    }
 }
@@ -1,26 +1,192 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use risty_core::{NtpClock, Unmarshal};
+use risty_proto::rtp::rtcp::compound::{CompoundRtcp, Feedback, Report, SubPackets};
+use risty_proto::rtp::rtcp::feedback::rist::RttEcho;
+use risty_proto::rtp::rtcp::packet::RtcpPacket;
+use risty_proto::rtp::rtcp::report_block::ReportBlock;
+use risty_proto::rtp::rtcp::sdes::{Chunk, Sdes};
+use risty_proto::rtp::rtcp::sender_report::{SenderInfo, SenderReport};
+
+use crate::common::RistListenerPort;
+use crate::error::{ConfigError, RistError};
+use crate::rtcp;
+use crate::rtt::RttEstimator;
+use crate::session::{self, Session};
+
 pub struct RtcpConfig {
     // RTCP Config.
     /// The sender may choose any arbitrary source port M for the RTP flow
     /// RIST senders may offer the user the ability to manually configure source ports M
-    rtcp_listener_port: u16,
+    pub rtcp_listener_port: u16,
+
+    /// Local UDP port the RTCP packets are sent from. The receiver sends its RTCP packets back to it.
+    pub rtcp_source_port: u16,
+
+    /// Canonical name carried in the SDES packets, typically "user@host".
+    pub cname: String,
+
+    pub rtcp: rtcp::Config,
+}
+
+impl RtcpConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        RistListenerPort::new(self.rtcp_listener_port)?;
+        rtcp::validate_cname(&self.cname)?;
+        self.rtcp.validate()
+    }
 }
 
 pub struct RtcpSender {
-    config: Config,
+    config: RtcpConfig,
+
+    scheduler: rtcp::Scheduler,
+
+    /// Last report block received from the receiver about our media.
+    last_report_block: Option<ReportBlock>,
+
+    rtt: RttEstimator,
+
+    /// Echo requests of the receiver waiting for a response, with the instant they were received.
+    echo_requests: VecDeque<(RttEcho, Instant)>,
+
+    session: Session,
 }
 
 impl RtcpSender {
-    pub fn new(config: Config) -> Self {}
+    /// - `multicast` is set when the receivers are the members of a multicast group.
+    pub fn new(config: RtcpConfig, ntp_clock: NtpClock, multicast: bool) -> Self {
+        Self {
+            scheduler: rtcp::Scheduler::new(config.rtcp.interval),
+            session: Session::new(
+                session::Config {
+                    stale_timeout: config.rtcp.session.stale_timeout,
+                    peer_timeout: config.rtcp.session.peer_timeout,
+                },
+                multicast,
+            ),
+            config,
+            last_report_block: None,
+            rtt: RttEstimator::new(ntp_clock),
+            echo_requests: VecDeque::new(),
+        }
+    }
 
     /// RIST senders shall periodically transmit the compound RTCP packets specified in section
     /// 5.2.1 to the configured IP address of the RIST receiver and UDP port P+1
     pub fn rtcp_receiver_port(&self) -> u16 {
-        self.rtcp_listener_port + 1
+        self.config.rtcp_listener_port + 1
+    }
+
+    pub fn rtcp_source_port(&self) -> u16 {
+        self.config.rtcp_source_port
+    }
+
+    pub fn last_report_block(&self) -> Option<&ReportBlock> {
+        self.last_report_block.as_ref()
+    }
+
+    pub fn rtt(&self) -> &RttEstimator {
+        &self.rtt
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn session_mut(&mut self) -> &mut Session {
+        &mut self.session
     }
 
     /// this function shall be called when receiving a packet on the rtcp socket
-    pub fn handle_rtcp_input(&mut self, packet: &[u8]) {}
+    /// - Returns the sub-packets of the compound packet, for the caller to act on the feedback messages.
+    pub fn handle_rtcp_input<'a>(
+        &mut self,
+        packet: &'a [u8],
+        source: SocketAddr,
+        now: Instant,
+    ) -> Result<Vec<RtcpPacket<'a>>, RistError> {
+        // Validates the ordering of the compound packet before looking at its content.
+        CompoundRtcp::unmarshal(packet)?;
+        let packets = SubPackets::new(packet)
+            .map(|sub_packet| sub_packet.and_then(|(_, bytes)| RtcpPacket::unmarshal(bytes)))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Receivers start their compound packets with a RR. Packets starting with a SR come from a sender of a
+        // multicast group, possibly this one through the loopback.
+        if matches!(packets.first(), Some(RtcpPacket::SenderReport(_))) {
+            return Ok(vec![]);
+        }
+        self.session.on_rtcp(source, &packets, now);
+
+        for packet in &packets {
+            match packet {
+                RtcpPacket::ReceiverReport(rr) => {
+                    if let Some(block) = rr.report_block.first() {
+                        // Receivers supporting RTT echoes measure the RTT themselves.
+                        if !self.config.rtcp.enable_rtt_echoes {
+                            self.rtt.on_report_block(block, now);
+                        }
+                        self.last_report_block = Some(block.clone());
+                    }
+                }
+                RtcpPacket::RttEchoRequest(request) => {
+                    self.echo_requests.push_back((request.clone(), now));
+                }
+                RtcpPacket::RttEchoResponse(response) => self.rtt.on_echo_response(response, now),
+                _ => {}
+            }
+        }
+
+        Ok(packets)
+    }
+
+    /// Instant the next sender report is due or the session times out, or the past if echo requests are
+    /// waiting for a response.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        match self.echo_requests.front() {
+            Some((_, received_at)) => Some(*received_at),
+            None => self
+                .scheduler
+                .poll_timeout()
+                .into_iter()
+                .chain(self.session.poll_timeout())
+                .min(),
+        }
+    }
 
     /// this function shall be called to send a rtcp packet to the receiver
-    pub fn poll_rtcp_transmit(&mut self) {}
+    /// - Returns the compound SR and SDES packet when a report is due at `now`. The responses to the echo
+    ///   requests received are sent right away, along with a report.
+    pub fn poll_rtcp_transmit(
+        &mut self,
+        ssrc: u32,
+        sender_info: impl FnOnce() -> SenderInfo,
+        now: Instant,
+    ) -> Result<Option<Vec<u8>>, RistError> {
+        self.session.handle_timeout(now);
+        let report_due = self.scheduler.poll_report(now);
+        if !report_due && self.echo_requests.is_empty() {
+            return Ok(None);
+        }
+
+        let echo_responses = self
+            .echo_requests
+            .drain(..)
+            .map(|(request, received_at)| RttEstimator::echo_response(&request, received_at, now))
+            .collect::<Vec<_>>();
+
+        let sr = SenderReport::new_with_sender_info(ssrc, sender_info());
+        let sdes = Sdes::new(Chunk::new(ssrc, self.config.cname.clone()));
+        let compound = echo_responses
+            .iter()
+            .fold(
+                CompoundRtcp::builder(Report::Sender(&sr), &sdes),
+                |builder, response| builder.push(Feedback::RttEcho(response)),
+            )
+            .build()?;
+
+        Ok(Some(compound.as_bytes().to_vec()))
+    }
 }
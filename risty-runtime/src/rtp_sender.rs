@@ -1,43 +1,402 @@
-use std::net::IpAddr;
+use std::collections::{HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
 
-use crate::common::RistListenerPort;
+use risty_core::{Marshal, NtpClock};
+use risty_proto::rtp::packet::RtpPacket;
+use risty_proto::rtp::rtcp::packet::RtcpPacket;
+
+use crate::common::{RistListenerPort, Transmit, MAX_DATAGRAM_SIZE};
+use crate::error::{BufferOverflow, ConfigError, RistError};
+use crate::retransmission::{Lookup, RetransmissionBuffer, RetransmissionStats};
+use crate::rtcp_sender::{RtcpConfig, RtcpSender};
+use crate::rtt::RttEstimator;
+use crate::session::{Session, SessionEvent};
+use crate::stats::SenderStats;
+
+/// Size of the header of the RTP packets sent, without CSRC nor header extension.
+const RTP_HEADER_SIZE: usize = 12;
+
+/// Largest payload that fits in a single datagram.
+pub const MAX_PAYLOAD_SIZE: usize = MAX_DATAGRAM_SIZE - RTP_HEADER_SIZE;
+
+/// Packets looked up at most per RTCP packet received. A single range can request 65536 packets, a bogus or
+/// hostile receiver would otherwise flood the network. Packets past the limit are requested again by the
+/// receiver at its next retry.
+const MAX_RETRANSMISSIONS_PER_REQUEST: usize = 1024;
+
+pub(crate) fn check_payload_size(payload: &[u8], max: usize) -> Result<(), RistError> {
+    if payload.len() > max {
+        return Err(RistError::BufferOverflow(BufferOverflow::PayloadTooLarge {
+            size: payload.len(),
+            max,
+        }));
+    }
+    Ok(())
+}
 
 pub struct RtpConfig {
     // RTP Config
-    rtp_source_port: u16, // M
-    rtp_pt: u8,
-    rtp_peer_port: RistListenerPort,
-    peer_address: IpAddr,
+    pub rtp_source_port: u16, // M
+    pub rtp_pt: u8,
+    pub rtp_peer_port: RistListenerPort,
+    pub peer_address: IpAddr,
 
-    // RTCP Config.
-    /// The sender may choose any arbitrary source port M for the RTP flow
-    /// RIST senders may offer the user the ability to manually configure source ports M
-    rtcp_listener_port: u16,
+    /// Synchronization source of the original packets. Its LSB is reserved to flag retransmissions and is
+    /// forced to 0.
+    pub ssrc: u32,
+
+    /// Frequency of the RTP timestamp clock in Hz, `MPEG_TS_CLOCK_RATE` for MPEG-2 TS payloads.
+    pub clock_rate: u32,
 
     // Buffer Config.
-    buffer_size: u64,
+    /// How long sent packets are kept to answer retransmission requests.
+    pub buffer_size: Duration,
 }
 
+impl RtpConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.clock_rate == 0 {
+            return Err(ConfigError::Zero { name: "clock rate" });
+        }
+        if self.buffer_size.is_zero() {
+            return Err(ConfigError::Zero {
+                name: "buffer size",
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Sans-IO state machine of the sender. The application pushes payloads and the RTCP datagrams received,
+/// along with the current time, and polls the datagrams to send and the instant it shall be woken up at.
+/// It never touches a socket or reads the clock, so it can be driven by any runtime or by a simulated network.
 pub struct RtpSender {
     config: RtpConfig,
+    rtcp_sender: RtcpSender,
+    stats: SenderStats,
+
+    /// Sequence number of the next packet.
+    sequence_number: u16,
+
+    /// RTP timestamp of the first packet and the instant it was pushed, RTP timestamps follow the clock rate from there.
+    timestamp_origin: Option<(Instant, u32)>,
+    initial_timestamp: u32,
+
+    retransmission_buffer: RetransmissionBuffer,
+
+    rtp_transmits: VecDeque<Transmit>,
 }
 
 impl RtpSender {
-    pub fn new(config: RtpConfig) -> Self {
-        Self { config }
+    pub fn new(
+        config: RtpConfig,
+        rtcp_config: RtcpConfig,
+        now: Instant,
+    ) -> Result<Self, RistError> {
+        config.validate()?;
+        rtcp_config.validate()?;
+
+        let ntp_clock = NtpClock::new(now);
+        Ok(Self {
+            stats: SenderStats::new(config.clock_rate, ntp_clock),
+            rtcp_sender: RtcpSender::new(
+                rtcp_config,
+                ntp_clock,
+                config.peer_address.is_multicast(),
+            ),
+            // The initial sequence number and timestamp are random to make known-plaintext attacks harder.
+            sequence_number: rand::random(),
+            timestamp_origin: None,
+            initial_timestamp: rand::random(),
+            retransmission_buffer: RetransmissionBuffer::new(config.buffer_size),
+            rtp_transmits: VecDeque::new(),
+            config: RtpConfig {
+                ssrc: config.ssrc & !1,
+                ..config
+            },
+        })
+    }
+
+    pub fn stats(&self) -> &SenderStats {
+        &self.stats
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.config.ssrc
+    }
+
+    pub fn retransmission_stats(&self) -> RetransmissionStats {
+        self.retransmission_buffer.stats()
+    }
+
+    /// Round-trip time to the receiver.
+    pub fn rtt(&self) -> &RttEstimator {
+        self.rtcp_sender.rtt()
+    }
+
+    /// Liveness and identity of the receiver.
+    pub fn session(&self) -> &Session {
+        self.rtcp_sender.session()
+    }
+
+    /// Closes the session, the RTCP packets of the receiver are ignored from now on.
+    pub fn close(&mut self, now: Instant) {
+        self.rtcp_sender.session_mut().close(now);
+    }
+
+    pub fn poll_event(&mut self) -> Option<SessionEvent> {
+        self.rtcp_sender.session_mut().poll_event()
     }
 
     /// RIST senders shall periodically transmit the compound RTCP packets specified in section
     /// 5.2.1 to the configured IP address of the RIST receiver and UDP port P+1
     pub fn rtcp_receiver_port(&self) -> u16 {
-        self.rtcp_listener_port + 1
+        self.rtcp_sender.rtcp_receiver_port()
+    }
+
+    /// Packetizes `payload` in a RTP data packet, to be sent at the next poll.
+    pub fn push_payload(&mut self, payload: &[u8], now: Instant) -> Result<(), RistError> {
+        check_payload_size(payload, MAX_PAYLOAD_SIZE)?;
+
+        let (origin, origin_timestamp) = *self
+            .timestamp_origin
+            .get_or_insert((now, self.initial_timestamp));
+        let ticks = now.saturating_duration_since(origin).as_nanos()
+            * self.config.clock_rate as u128
+            / 1_000_000_000;
+        let timestamp = origin_timestamp.wrapping_add(ticks as u32);
+
+        let packet = RtpPacket::new(
+            self.config.rtp_pt,
+            self.sequence_number,
+            timestamp,
+            self.config.ssrc,
+            payload,
+        );
+        let mut bytes = vec![0; packet.marshal_size()];
+        packet.marshal(&mut bytes)?;
+
+        self.retransmission_buffer
+            .insert(self.sequence_number, bytes.clone(), now);
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+        self.stats.on_packet_sent(timestamp, payload.len(), now);
+        self.queue_rtp(bytes);
+
+        Ok(())
     }
 
     /// this function shall be called when receiving a packet on the rtcp socket
-    pub fn handle_rtcp_input(&mut self, packet: &[u8]) {}
+    /// - `source` is the address the packet was received from.
+    pub fn handle_rtcp_input(
+        &mut self,
+        packet: &[u8],
+        source: SocketAddr,
+        now: Instant,
+    ) -> Result<(), RistError> {
+        let mut requested = vec![];
+        // The same packet may be requested by several NACKs, or by overlapping ranges, of a compound packet.
+        let mut seen = HashSet::new();
+        for packet in self.rtcp_sender.handle_rtcp_input(packet, source, now)? {
+            let sequence_numbers: Box<dyn Iterator<Item = u16>> = match &packet {
+                RtcpPacket::GenericNack(nack) if self.is_own_ssrc(nack.ssrc_media_src) => {
+                    Box::new(nack.fcis.iter().flat_map(|fci| fci.sequence_numbers()))
+                }
+                RtcpPacket::RangeBasedNACK(nack) if self.is_own_ssrc(nack.ssrc) => Box::new(
+                    nack.packet_ranges
+                        .iter()
+                        .flat_map(|range| range.sequence_numbers()),
+                ),
+                _ => continue,
+            };
+            for seq in sequence_numbers {
+                if requested.len() == MAX_RETRANSMISSIONS_PER_REQUEST {
+                    break;
+                }
+                if seen.insert(seq) {
+                    requested.push(seq);
+                }
+            }
+        }
+
+        for seq in requested {
+            // Packets that aged out are counted in the retransmission stats, there is nothing else to do.
+            if let Lookup::Found(bytes) = self.retransmission_buffer.lookup(seq, now) {
+                self.queue_rtp(bytes);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The receiver may use either the original or the retransmission SSRC in its requests.
+    fn is_own_ssrc(&self, ssrc: u32) -> bool {
+        ssrc & !1 == self.config.ssrc
+    }
+
+    fn queue_rtp(&mut self, bytes: Vec<u8>) {
+        self.rtp_transmits.push_back(Transmit {
+            destination: SocketAddr::new(self.config.peer_address, self.config.rtp_peer_port.get()),
+            source_port: self.config.rtp_source_port,
+            bytes,
+        });
+    }
 
     /// this function shall be called to send a rtcp packet to the receiver
-    pub fn poll_rtcp_transmit(&mut self) {}
+    pub fn poll_rtcp_transmit(&mut self, now: Instant) -> Result<Option<Transmit>, RistError> {
+        let stats = &self.stats;
+        let bytes = self.rtcp_sender.poll_rtcp_transmit(
+            self.config.ssrc,
+            || stats.sender_info(now),
+            now,
+        )?;
+
+        Ok(bytes.map(|bytes| Transmit {
+            destination: SocketAddr::new(
+                self.config.peer_address,
+                self.rtcp_sender.rtcp_receiver_port(),
+            ),
+            source_port: self.rtcp_sender.rtcp_source_port(),
+            bytes,
+        }))
+    }
+
+    pub fn poll_rtp_transmit(&mut self) -> Option<Transmit> {
+        self.rtp_transmits.pop_front()
+    }
+
+    /// Instant the state machine shall be polled again at, even if nothing was received.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.rtcp_sender.poll_timeout()
+    }
+}
 
-    pub fn poll_rtp_transmit(&mut self) {}
+#[cfg(test)]
+mod tests {
+    use risty_core::Unmarshal;
+    use risty_proto::rtp::rtcp::compound::{CompoundRtcp, Feedback, Report};
+    use risty_proto::rtp::rtcp::feedback::nack::{Fci, GenericNack};
+    use risty_proto::rtp::rtcp::feedback::rist::{PacketRangeRequest, RangeBasedNACK};
+    use risty_proto::rtp::rtcp::receiver_report::ReceiverReport;
+    use risty_proto::rtp::rtcp::sdes::{Chunk, Sdes};
+
+    use super::*;
+    use crate::{rtcp, session};
+
+    const SSRC: u32 = 0x1234_5678;
+
+    fn sender(now: Instant) -> RtpSender {
+        let config = RtpConfig {
+            rtp_source_port: 5000,
+            rtp_pt: 33,
+            rtp_peer_port: RistListenerPort::new(6000).unwrap(),
+            peer_address: "127.0.0.1".parse().unwrap(),
+            ssrc: SSRC,
+            clock_rate: 90_000,
+            buffer_size: Duration::from_secs(1),
+        };
+        let rtcp_config = RtcpConfig {
+            rtcp_listener_port: 6000,
+            rtcp_source_port: 5001,
+            cname: "sender@test".to_string(),
+            rtcp: rtcp::Config {
+                interval: Duration::from_millis(100),
+                enable_rtt_echoes: false,
+                session: session::Config {
+                    stale_timeout: Duration::from_millis(300),
+                    peer_timeout: Duration::from_millis(800),
+                },
+            },
+        };
+        RtpSender::new(config, rtcp_config, now).unwrap()
+    }
+
+    /// Sequence numbers of the packets waiting to be sent.
+    fn drain(sender: &mut RtpSender) -> Vec<u16> {
+        std::iter::from_fn(|| sender.poll_rtp_transmit())
+            .map(|transmit| {
+                RtpPacket::unmarshal(&transmit.bytes)
+                    .unwrap()
+                    .header
+                    .sequence_number
+            })
+            .collect()
+    }
+
+    fn nacks(feedbacks: Vec<Feedback>) -> Vec<u8> {
+        let rr = ReceiverReport::new_empty(0x9abc_def0);
+        let sdes = Sdes::new(Chunk::new(0x9abc_def0, "receiver@test".to_string()));
+        let builder = feedbacks.into_iter().fold(
+            CompoundRtcp::builder(Report::Receiver(&rr), &sdes),
+            |builder, feedback| builder.push(feedback),
+        );
+        builder.build().unwrap().as_bytes().to_vec()
+    }
+
+    #[test]
+    fn packets_requested_twice_are_sent_once() {
+        let now = Instant::now();
+        let source = "127.0.0.1:6001".parse().unwrap();
+        let mut sender = sender(now);
+        for _ in 0..20 {
+            sender.push_payload(&[0; 188], now).unwrap();
+        }
+        let sent = drain(&mut sender);
+
+        let first = sent[0];
+        let fcis = [
+            Fci {
+                pid: first.wrapping_add(2),
+                blp: 0b11,
+            },
+            Fci {
+                pid: first.wrapping_add(3),
+                blp: 0,
+            },
+        ];
+        let generic = GenericNack::new(SSRC, &fcis[..]);
+        let ranges = [PacketRangeRequest {
+            seq_start: first.wrapping_add(4),
+            nb_consecutive: 2,
+        }];
+        let range = RangeBasedNACK::new(SSRC | 1, &ranges[..]);
+        let request = nacks(vec![
+            Feedback::GenericNack(&generic),
+            Feedback::RangeBasedNACK(&range),
+        ]);
+        sender.handle_rtcp_input(&request, source, now).unwrap();
+
+        assert_eq!(
+            drain(&mut sender),
+            (2..7)
+                .map(|offset| first.wrapping_add(offset))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn retransmissions_are_bounded_per_request() {
+        let now = Instant::now();
+        let source = "127.0.0.1:6001".parse().unwrap();
+        let mut sender = sender(now);
+        for _ in 0..2000 {
+            sender.push_payload(&[0; 188], now).unwrap();
+        }
+        let first = drain(&mut sender)[0];
+
+        let ranges = [PacketRangeRequest {
+            seq_start: first,
+            nb_consecutive: u16::MAX,
+        }];
+        let range = RangeBasedNACK::new(SSRC, &ranges[..]);
+        sender
+            .handle_rtcp_input(&nacks(vec![Feedback::RangeBasedNACK(&range)]), source, now)
+            .unwrap();
+
+        assert_eq!(drain(&mut sender).len(), MAX_RETRANSMISSIONS_PER_REQUEST);
+        assert_eq!(
+            sender.retransmission_stats().requested,
+            MAX_RETRANSMISSIONS_PER_REQUEST as u64
+        );
+    }
 }
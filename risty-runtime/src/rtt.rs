@@ -0,0 +1,141 @@
+use std::time::{Duration, Instant};
+
+use risty_core::{compact_to_duration, duration_to_compact, NtpClock, NtpTime};
+use risty_proto::rtp::rtcp::feedback::rist::RttEcho;
+use risty_proto::rtp::rtcp::report_block::ReportBlock;
+
+/// Measures the round-trip time to the peer, either with RTT echo request/response packets or, when the
+/// peer does not support them, with the LSR and DLSR fields of the report blocks (RFC 3550 section 6.4.1) on
+/// the sender, and with the time retransmissions take to answer their request on the receiver.
+/// Samples are smoothed as in RFC 6298.
+pub struct RttEstimator {
+    ntp_clock: NtpClock,
+
+    latest: Option<Duration>,
+    smoothed: Option<Duration>,
+    variance: Duration,
+    min: Option<Duration>,
+
+    /// Compact NTP timestamp of the last sender report received and the instant it was received, to fill
+    /// the LSR and DLSR fields of the report blocks.
+    last_sr: Option<(u32, Instant)>,
+}
+
+impl RttEstimator {
+    pub fn new(ntp_clock: NtpClock) -> Self {
+        Self {
+            ntp_clock,
+            latest: None,
+            smoothed: None,
+            variance: Duration::ZERO,
+            min: None,
+            last_sr: None,
+        }
+    }
+
+    /// Most recent sample.
+    pub fn latest(&self) -> Option<Duration> {
+        self.latest
+    }
+
+    /// Smoothed round-trip time, `None` until a first sample is taken.
+    pub fn smoothed(&self) -> Option<Duration> {
+        self.smoothed
+    }
+
+    /// Smallest sample, the round-trip time without queuing.
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    /// Mean deviation of the samples from the smoothed round-trip time.
+    pub fn variance(&self) -> Duration {
+        self.variance
+    }
+
+    /// Echo request to send at `now`, carrying the NTP time of `now`.
+    pub fn echo_request(&self, ssrc: u32, now: Instant) -> RttEcho {
+        RttEcho::new_request(ssrc, 0, self.ntp_clock.ntp_time(now))
+    }
+
+    /// Response to an echo request of the peer.
+    /// - `received_at` is the instant the request was received, the time spent since then is reported as
+    ///   processing delay so that it is not counted in the peer's measurement.
+    pub fn echo_response(request: &RttEcho, received_at: Instant, now: Instant) -> RttEcho {
+        let processing_delay = NtpTime::from_duration(now.saturating_duration_since(received_at));
+        RttEcho::new_response(
+            request.ssrc,
+            request.timestamp,
+            processing_delay.into(),
+            request.padding_size,
+        )
+    }
+
+    /// Takes a sample from the response to one of our echo requests.
+    pub fn on_echo_response(&mut self, response: &RttEcho, now: Instant) {
+        let sent_at = NtpTime::from(response.timestamp);
+        let processing_delay = NtpTime::from(response.processing_delay).as_duration();
+        let rtt = self
+            .ntp_clock
+            .ntp_time(now)
+            .saturating_duration_since(sent_at)
+            .saturating_sub(processing_delay);
+        self.sample(rtt);
+    }
+
+    /// Takes a sample from a report block about our media, as long as the peer received a sender report.
+    pub fn on_report_block(&mut self, block: &ReportBlock, now: Instant) {
+        if block.last_sr_timestamp == 0 {
+            return;
+        }
+        let arrival = self.ntp_clock.ntp_time(now).compact();
+        let rtt = arrival
+            .wrapping_sub(block.last_sr_timestamp)
+            .wrapping_sub(block.delay_since_last_sr);
+        // A negative value means the report is bogus or the clock went backwards.
+        if rtt <= i32::MAX as u32 {
+            self.sample(compact_to_duration(rtt));
+        }
+    }
+
+    /// Takes a sample from a retransmission answering the only request made for the packet. Packets requested
+    /// several times are skipped, there is no telling which request was answered (Karn's algorithm). The sample
+    /// includes the time the peer took to answer.
+    pub fn on_retransmission(&mut self, requests: &[Instant], now: Instant) {
+        if let [requested_at] = requests {
+            self.sample(now.saturating_duration_since(*requested_at));
+        }
+    }
+
+    /// Records a sender report of the peer, for the next report blocks.
+    pub fn on_sender_report(&mut self, ntp_time: NtpTime, now: Instant) {
+        self.last_sr = Some((ntp_time.compact(), now));
+    }
+
+    /// LSR and DLSR fields of a report block sent at `now`, zero until a sender report is received.
+    pub fn last_sr(&self, now: Instant) -> (u32, u32) {
+        match self.last_sr {
+            Some((lsr, received_at)) => (
+                lsr,
+                duration_to_compact(now.saturating_duration_since(received_at)),
+            ),
+            None => (0, 0),
+        }
+    }
+
+    fn sample(&mut self, rtt: Duration) {
+        self.latest = Some(rtt);
+        self.min = Some(self.min.map_or(rtt, |min| min.min(rtt)));
+        match self.smoothed {
+            Some(smoothed) => {
+                let deviation = smoothed.abs_diff(rtt);
+                self.variance = (self.variance * 3 + deviation) / 4;
+                self.smoothed = Some((smoothed * 7 + rtt) / 8);
+            }
+            None => {
+                self.variance = rtt / 2;
+                self.smoothed = Some(rtt);
+            }
+        }
+    }
+}
@@ -1,23 +1,240 @@
-use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::time::Instant;
 
-use crate::rtcp_sender::{RtcpConfig, RtcpSender};
+use crate::common::Transmit;
+use crate::error::{ConfigError, RistError};
+use crate::multicast;
+use crate::rtcp_sender::RtcpConfig;
 use crate::rtp_sender::{RtpConfig, RtpSender};
+use crate::session::{Session, SessionEvent};
 
-struct SenderConfig {
-    rtp_config: RtpConfig,
-    rtcp_config: RtcpConfig,
+pub struct SenderConfig {
+    pub rtp_config: RtpConfig,
+    pub rtcp_config: RtcpConfig,
+
+    /// Options of the packets sent when the peer address is a multicast group, `None` keeps the defaults of the
+    /// system.
+    pub multicast: Option<multicast::Options>,
+}
+
+impl SenderConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.rtp_config.validate()?;
+        self.rtcp_config.validate()?;
+
+        let group = self.rtp_config.peer_address;
+        if !group.is_multicast() {
+            return match self.multicast {
+                Some(_) => Err(ConfigError::NotMulticast(group)),
+                None => Ok(()),
+            };
+        }
+        // The sender listens to the RTCP packets the receivers send to the group.
+        let expected = self.rtcp_config.rtcp_listener_port + 1;
+        if self.rtcp_config.rtcp_source_port != expected {
+            return Err(ConfigError::MulticastRtcpPort {
+                port: self.rtcp_config.rtcp_source_port,
+                expected,
+            });
+        }
+        match &self.multicast {
+            Some(options) => options.validate(group),
+            None => Ok(()),
+        }
+    }
 }
 
+/// RIST sender. This is a sans-IO state machine, see [`RtpSender`] for how to drive it.
 pub struct Sender {
     rtp_sender: RtpSender,
-    rtcp_sender: RtcpSender,
 }
 
 impl Sender {
-    pub fn new(config: SenderConfig) -> Self {
-        Self {
-            rtp_sender: RtpSender::new(config.rtp_config),
-            rtcp_sender: RtcpSender::new(config.rtcp_config),
+    pub fn new(config: SenderConfig, now: Instant) -> Result<Self, RistError> {
+        config.validate()?;
+        Ok(Self {
+            rtp_sender: RtpSender::new(config.rtp_config, config.rtcp_config, now)?,
+        })
+    }
+
+    pub fn rtp_sender(&self) -> &RtpSender {
+        &self.rtp_sender
+    }
+
+    /// Liveness and identity of the receiver.
+    pub fn session(&self) -> &Session {
+        self.rtp_sender.session()
+    }
+
+    pub fn close(&mut self, now: Instant) {
+        self.rtp_sender.close(now);
+    }
+
+    /// Next session event, the session timeouts are checked by [`Sender::poll_transmit`].
+    pub fn poll_event(&mut self) -> Option<SessionEvent> {
+        self.rtp_sender.poll_event()
+    }
+
+    /// Queues a media payload, typically 7 MPEG-2 TS packets of 188 bytes.
+    pub fn push(&mut self, payload: &[u8], now: Instant) -> Result<(), RistError> {
+        self.rtp_sender.push_payload(payload, now)
+    }
+
+    /// this function shall be called when receiving a packet on the rtcp socket
+    pub fn handle_rtcp_input(
+        &mut self,
+        packet: &[u8],
+        source: SocketAddr,
+        now: Instant,
+    ) -> Result<(), RistError> {
+        self.rtp_sender.handle_rtcp_input(packet, source, now)
+    }
+
+    /// Next datagram to send, RTCP packets first so that reports are not delayed by a burst of media.
+    pub fn poll_transmit(&mut self, now: Instant) -> Result<Option<Transmit>, RistError> {
+        if let Some(transmit) = self.rtp_sender.poll_rtcp_transmit(now)? {
+            return Ok(Some(transmit));
+        }
+        Ok(self.rtp_sender.poll_rtp_transmit())
+    }
+
+    /// Instant [`Sender::poll_transmit`] shall be called at, even if nothing was pushed or received.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.rtp_sender.poll_timeout()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use risty_proto::rtp::rtcp::feedback::encoder::NackFormats;
+
+    use super::*;
+    use crate::common::RistListenerPort;
+    use crate::receive_buffer::PlayoutReference;
+    use crate::receiver::{self, Receiver};
+    use crate::{rtcp, session};
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    /// One-way delay of the simulated network.
+    const DELAY: Duration = Duration::from_millis(5);
+
+    fn rtcp_config() -> rtcp::Config {
+        rtcp::Config {
+            interval: Duration::from_millis(100),
+            enable_rtt_echoes: false,
+            session: session::Config {
+                stale_timeout: Duration::from_millis(300),
+                peer_timeout: Duration::from_millis(800),
+            },
         }
     }
+
+    fn sender(now: Instant) -> Sender {
+        let config = SenderConfig {
+            rtp_config: RtpConfig {
+                rtp_source_port: 5000,
+                rtp_pt: 33,
+                rtp_peer_port: RistListenerPort::new(6000).unwrap(),
+                peer_address: LOCALHOST,
+                ssrc: 0x1234_5678,
+                clock_rate: 90_000,
+                buffer_size: Duration::from_secs(1),
+            },
+            rtcp_config: RtcpConfig {
+                rtcp_listener_port: 6000,
+                rtcp_source_port: 5001,
+                cname: "sender@test".to_string(),
+                rtcp: rtcp_config(),
+            },
+            multicast: None,
+        };
+        Sender::new(config, now).unwrap()
+    }
+
+    fn receiver(now: Instant) -> Receiver {
+        let config = receiver::Config {
+            buffer_size: Duration::from_millis(200),
+            reorder_section: Duration::from_millis(5),
+            playout_reference: PlayoutReference::Arrival,
+            max_number_of_retry_per_packet: 3,
+            clock_rate: 90_000,
+            cname: "receiver@test".to_string(),
+            rtcp: rtcp_config(),
+            nack_formats: NackFormats::Any,
+            multicast: None,
+            sender_address: None,
+        };
+        Receiver::new(RistListenerPort::new(6000).unwrap(), config, now).unwrap()
+    }
+
+    #[test]
+    fn lost_packet_is_recovered_with_a_nack() {
+        let start = Instant::now();
+        let mut sender = sender(start);
+        let mut receiver = receiver(start);
+
+        // Datagrams in flight, with the instant they arrive at.
+        let mut to_receiver: VecDeque<(Instant, Transmit)> = VecDeque::new();
+        let mut to_sender: VecDeque<(Instant, Vec<u8>)> = VecDeque::new();
+        let mut lost = false;
+        let mut payloads = vec![];
+
+        for ms in 0..500 {
+            let now = start + Duration::from_millis(ms);
+            if ms < 100 {
+                sender.push(&(ms as u16).to_be_bytes(), now).unwrap();
+            }
+
+            while let Some(transmit) = sender.poll_transmit(now).unwrap() {
+                // The original of the 11th packet is lost.
+                if ms == 10 && transmit.source_port == 5000 && !lost {
+                    lost = true;
+                    continue;
+                }
+                to_receiver.push_back((now + DELAY, transmit));
+            }
+            while to_receiver.front().is_some_and(|(at, _)| *at <= now) {
+                let (_, transmit) = to_receiver.pop_front().unwrap();
+                let source = SocketAddr::new(LOCALHOST, transmit.source_port);
+                match transmit.destination.port() {
+                    6000 => {
+                        receiver
+                            .handle_rtp_input(&transmit.bytes, source, now)
+                            .unwrap();
+                    }
+                    _ => receiver
+                        .handle_rtcp_input(&transmit.bytes, source, now)
+                        .unwrap(),
+                }
+            }
+
+            while let Some(bytes) = receiver.poll_rtcp_transmit(now).unwrap() {
+                to_sender.push_back((now + DELAY, bytes));
+            }
+            while to_sender.front().is_some_and(|(at, _)| *at <= now) {
+                let (_, bytes) = to_sender.pop_front().unwrap();
+                sender
+                    .handle_rtcp_input(&bytes, SocketAddr::new(LOCALHOST, 6001), now)
+                    .unwrap();
+            }
+
+            payloads.extend(std::iter::from_fn(|| receiver.poll_payload(now)));
+        }
+
+        assert!(lost);
+        assert_eq!(
+            payloads,
+            (0..100u16)
+                .map(|ms| ms.to_be_bytes().to_vec())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(receiver.stats().packets_recovered, 1);
+        assert_eq!(receiver.stats().packets_lost, 0);
+        assert_eq!(sender.rtp_sender().retransmission_stats().retransmitted, 1);
+    }
 }
@@ -0,0 +1,263 @@
+/// A source is declared valid only after this many packets have been received in sequence.
+const MIN_SEQUENTIAL: u16 = 2;
+
+/// Largest forward jump in sequence numbers still considered as packet loss rather than a restart.
+const MAX_DROPOUT: u16 = 3000;
+
+/// Largest backward jump in sequence numbers still considered as a reordered or duplicated packet.
+const MAX_MISORDER: u16 = 100;
+
+const RTP_SEQ_MOD: u64 = 1 << 16;
+
+/// Outcome of feeding a sequence number to the [`SequenceTracker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceUpdate {
+    /// The packet is accepted, with its extended sequence number. Reordered and duplicated packets are
+    /// also accepted, with the extended sequence number of their original position.
+    Valid(u64),
+
+    /// The source has not been validated yet, the packet shall not be used.
+    Probation,
+
+    /// The sequence number jumped too far away, the packet is dropped until the jump is confirmed by
+    /// the next packet.
+    Invalid,
+
+    /// The sender restarted with a new sequence, all the counters were reset and the extended sequence
+    /// numbers start over from the given value.
+    Restarted(u64),
+}
+
+/// Tracks the sequence numbers received from a source, as described in RFC 3550 Appendix A.1. It counts
+/// the sequence number cycles to produce extended sequence numbers, used both in the report blocks and
+/// to index the receive buffers.
+#[derive(Debug, Clone)]
+pub struct SequenceTracker {
+    /// Highest sequence number seen.
+    max_seq: u16,
+
+    /// Shifted count of sequence number cycles.
+    cycles: u64,
+
+    /// First sequence number of the current sequence.
+    base_seq: u16,
+
+    /// Last 'bad' sequence number + 1, `None` when no jump is pending.
+    bad_seq: Option<u16>,
+
+    /// Sequential packets still needed before the source is valid.
+    probation: u16,
+
+    min_sequential: u16,
+
+    /// Packets received since the sequence started.
+    received: u64,
+
+    initialized: bool,
+}
+
+impl Default for SequenceTracker {
+    fn default() -> Self {
+        Self::new(MIN_SEQUENTIAL)
+    }
+}
+
+impl SequenceTracker {
+    /// - `min_sequential` is the number of in sequence packets needed to validate the source, 0 accepts
+    ///   the first packet right away.
+    pub fn new(min_sequential: u16) -> Self {
+        Self {
+            max_seq: 0,
+            cycles: 0,
+            base_seq: 0,
+            bad_seq: None,
+            probation: min_sequential,
+            min_sequential,
+            received: 0,
+            initialized: false,
+        }
+    }
+
+    fn init_seq(&mut self, seq: u16) {
+        self.base_seq = seq;
+        self.max_seq = seq;
+        self.bad_seq = None;
+        self.cycles = 0;
+        self.received = 0;
+    }
+
+    pub fn update(&mut self, seq: u16) -> SequenceUpdate {
+        if !self.initialized {
+            self.initialized = true;
+            self.init_seq(seq);
+            if self.probation == 0 {
+                self.received += 1;
+                return SequenceUpdate::Valid(self.extend(seq));
+            }
+            self.max_seq = seq.wrapping_sub(1);
+        }
+
+        let udelta = seq.wrapping_sub(self.max_seq);
+
+        if self.probation > 0 {
+            // Packet is in sequence
+            if seq == self.max_seq.wrapping_add(1) {
+                self.probation -= 1;
+                self.max_seq = seq;
+                if self.probation == 0 {
+                    self.init_seq(seq);
+                    self.received += 1;
+                    return SequenceUpdate::Valid(self.extend(seq));
+                }
+            } else {
+                self.probation = self.min_sequential - 1;
+                self.max_seq = seq;
+            }
+            return SequenceUpdate::Probation;
+        }
+
+        if udelta < MAX_DROPOUT {
+            // In order, with permissible gap
+            if seq < self.max_seq {
+                // Sequence number wrapped, count another 64K cycle.
+                self.cycles += RTP_SEQ_MOD;
+            }
+            self.max_seq = seq;
+        } else if udelta as u64 <= RTP_SEQ_MOD - MAX_MISORDER as u64 {
+            // The sequence number made a very large jump
+            if Some(seq) == self.bad_seq {
+                // Two sequential packets, assume that the other side restarted without telling us.
+                self.init_seq(seq);
+                self.received += 1;
+                return SequenceUpdate::Restarted(self.extend(seq));
+            }
+            self.bad_seq = Some(seq.wrapping_add(1));
+            return SequenceUpdate::Invalid;
+        }
+        // Otherwise duplicate or reordered packet
+
+        self.received += 1;
+        SequenceUpdate::Valid(self.extend(seq))
+    }
+
+    /// Extended sequence number of `seq`, taken as the closest one to the highest sequence number seen,
+    /// so that sequence numbers slightly behind it across a wrap around belong to the previous cycle.
+    pub fn extend(&self, seq: u16) -> u64 {
+        let delta = seq.wrapping_sub(self.max_seq) as i16;
+        (self.cycles + self.max_seq as u64).saturating_add_signed(delta as i64)
+    }
+
+    /// Highest extended sequence number received: the count of cycles in the 16 most significant bits
+    /// and the highest sequence number in the 16 least significant bits, as carried in report blocks.
+    pub fn extended_max(&self) -> u32 {
+        (self.cycles + self.max_seq as u64) as u32
+    }
+
+    /// Number of packets expected since the sequence started, derived from the highest sequence number.
+    pub fn expected(&self) -> u64 {
+        if !self.is_valid() {
+            return 0;
+        }
+        self.cycles + self.max_seq as u64 - self.base_seq as u64 + 1
+    }
+
+    /// Number of packets received since the sequence started, including duplicates.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Whether the source has been validated and extended sequence numbers are meaningful.
+    pub fn is_valid(&self) -> bool {
+        self.initialized && self.probation == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tracker that already accepted `seq`.
+    fn tracker_at(seq: u16) -> SequenceTracker {
+        let mut tracker = SequenceTracker::new(0);
+        assert_eq!(tracker.update(seq), SequenceUpdate::Valid(seq as u64));
+        tracker
+    }
+
+    #[test]
+    fn source_is_valid_after_min_sequential_packets() {
+        let mut tracker = SequenceTracker::default();
+        assert_eq!(tracker.update(10), SequenceUpdate::Probation);
+        assert!(!tracker.is_valid());
+        // Out of sequence, the probation starts over from this packet.
+        assert_eq!(tracker.update(20), SequenceUpdate::Probation);
+        assert_eq!(tracker.update(21), SequenceUpdate::Valid(21));
+        assert!(tracker.is_valid());
+
+        // The sequence starts at the packet validating the source.
+        assert_eq!(tracker.expected(), 1);
+        assert_eq!(tracker.received(), 1);
+    }
+
+    #[test]
+    fn cycles_are_counted_across_the_wrap_around() {
+        let mut tracker = tracker_at(65534);
+        assert_eq!(tracker.update(65535), SequenceUpdate::Valid(65535));
+        assert_eq!(tracker.update(0), SequenceUpdate::Valid(65536));
+        assert_eq!(tracker.update(1), SequenceUpdate::Valid(65537));
+        assert_eq!(tracker.extended_max(), 0x0001_0001);
+
+        // Reordered from before the wrap around, it belongs to the previous cycle.
+        assert_eq!(tracker.update(65535), SequenceUpdate::Valid(65535));
+        assert_eq!(tracker.extended_max(), 0x0001_0001);
+        assert_eq!(tracker.expected(), 4);
+        assert_eq!(tracker.received(), 5);
+    }
+
+    #[test]
+    fn forward_jumps_beyond_max_dropout_are_invalid() {
+        let mut tracker = tracker_at(100);
+        assert_eq!(
+            tracker.update(100 + MAX_DROPOUT - 1),
+            SequenceUpdate::Valid((100 + MAX_DROPOUT - 1) as u64)
+        );
+
+        let max_seq = 100 + MAX_DROPOUT - 1;
+        assert_eq!(
+            tracker.update(max_seq + MAX_DROPOUT),
+            SequenceUpdate::Invalid
+        );
+        assert_eq!(tracker.extended_max(), max_seq as u32);
+        assert_eq!(
+            tracker.update(max_seq + 1),
+            SequenceUpdate::Valid(max_seq as u64 + 1)
+        );
+    }
+
+    #[test]
+    fn backward_jumps_beyond_max_misorder_are_invalid() {
+        let mut tracker = tracker_at(1000);
+        assert_eq!(
+            tracker.update(1000 - MAX_MISORDER + 1),
+            SequenceUpdate::Valid((1000 - MAX_MISORDER + 1) as u64)
+        );
+        assert_eq!(tracker.update(1000 - MAX_MISORDER), SequenceUpdate::Invalid);
+        assert_eq!(tracker.extended_max(), 1000);
+    }
+
+    #[test]
+    fn restart_is_detected_after_two_sequential_packets() {
+        let mut tracker = tracker_at(65535);
+        assert_eq!(tracker.update(0), SequenceUpdate::Valid(65536));
+
+        assert_eq!(tracker.update(30_000), SequenceUpdate::Invalid);
+        // Not following the jump, still a jump.
+        assert_eq!(tracker.update(40_000), SequenceUpdate::Invalid);
+        assert_eq!(tracker.update(40_001), SequenceUpdate::Restarted(40_001));
+
+        // Cycles and counters start over.
+        assert_eq!(tracker.extended_max(), 40_001);
+        assert_eq!(tracker.update(40_002), SequenceUpdate::Valid(40_002));
+        assert_eq!(tracker.expected(), 2);
+        assert_eq!(tracker.received(), 2);
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use risty_proto::rtp::rtcp::packet::RtcpPacket;
+
+use crate::error::{ConfigError, RistError};
+
+pub struct Config {
+    /// RTCP silence after which an established session is reported as stale. It shall be longer than the
+    /// RTCP interval, a few intervals is a good choice.
+    pub stale_timeout: Duration,
+
+    /// RTCP silence after which the peer is declared dead and the session closed.
+    pub peer_timeout: Duration,
+}
+
+impl Config {
+    pub fn validate(&self, rtcp_interval: Duration) -> Result<(), ConfigError> {
+        if self.stale_timeout <= rtcp_interval {
+            return Err(ConfigError::StaleTimeoutTooShort {
+                stale_timeout: self.stale_timeout,
+                rtcp_interval,
+            });
+        }
+        if self.peer_timeout <= self.stale_timeout {
+            return Err(ConfigError::PeerTimeoutTooShort {
+                peer_timeout: self.peer_timeout,
+                stale_timeout: self.stale_timeout,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    /// No RTCP packet was received from the peer yet.
+    Connecting,
+
+    /// The peer sends its RTCP packets.
+    Established,
+
+    /// The peer did not send any RTCP packet for the stale timeout, it may be back soon.
+    Stale,
+
+    /// The peer timed out, or the session was closed locally. The session stays closed and the drivers stop,
+    /// see [`Session::error`].
+    Closed,
+}
+
+/// Identity of the peer, learnt from its RTCP packets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Peer {
+    /// Source address of the last RTCP packet received.
+    pub address: Option<SocketAddr>,
+
+    /// SSRC of the SR or RR packets of the peer.
+    pub ssrc: Option<u32>,
+
+    /// CNAME carried in the SDES packets of the peer.
+    pub cname: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEvent {
+    StateChanged {
+        previous: SessionState,
+        state: SessionState,
+        at: Instant,
+    },
+
+    /// The peer changed its address, SSRC or CNAME. Not emitted when the peer is first learnt.
+    PeerChanged { previous: Peer, peer: Peer },
+}
+
+/// Liveness of the link to the peer, driven by the RTCP packets it sends. Like the other state machines, it is
+/// given the current time and polled for its timeout and for the events to report.
+pub struct Session {
+    config: Config,
+    state: SessionState,
+    peer: Peer,
+
+    /// Instant the last RTCP packet of the peer was received at.
+    last_rtcp: Option<Instant>,
+
+    /// The session was closed locally rather than by a peer timeout.
+    closed_locally: bool,
+
+    /// The peers are the receivers of a multicast group. Any of them keeps the session alive, and their
+    /// identity is not tracked.
+    multicast: bool,
+
+    events: VecDeque<SessionEvent>,
+}
+
+impl Session {
+    pub fn new(config: Config, multicast: bool) -> Self {
+        Self {
+            multicast,
+            config,
+            state: SessionState::Connecting,
+            peer: Peer::default(),
+            last_rtcp: None,
+            closed_locally: false,
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    pub fn peer(&self) -> &Peer {
+        &self.peer
+    }
+
+    /// Shall be called with the sub-packets of each valid compound RTCP packet received from the peer. They
+    /// are ignored once the session is closed.
+    pub fn on_rtcp(&mut self, source: SocketAddr, packets: &[RtcpPacket], now: Instant) {
+        if self.state == SessionState::Closed {
+            return;
+        }
+        if self.multicast {
+            self.last_rtcp = Some(now);
+            self.set_state(SessionState::Established, now);
+            return;
+        }
+
+        let mut peer = Peer {
+            address: Some(source),
+            ..self.peer.clone()
+        };
+        for packet in packets {
+            match packet {
+                RtcpPacket::SenderReport(sr) => peer.ssrc = Some(sr.ssrc_sender),
+                RtcpPacket::ReceiverReport(rr) => peer.ssrc = Some(rr.ssrc_sender),
+                RtcpPacket::Sdes(sdes) => peer.cname = Some(sdes.chunk.user_and_domain.clone()),
+                _ => {}
+            }
+        }
+        if peer != self.peer {
+            let previous = std::mem::replace(&mut self.peer, peer);
+            if self.state != SessionState::Connecting {
+                self.events.push_back(SessionEvent::PeerChanged {
+                    previous,
+                    peer: self.peer.clone(),
+                });
+            }
+        }
+
+        self.last_rtcp = Some(now);
+        self.set_state(SessionState::Established, now);
+    }
+
+    /// Shall be called when the instant returned by [`Session::poll_timeout`] is reached.
+    pub fn handle_timeout(&mut self, now: Instant) {
+        let Some(last_rtcp) = self.last_rtcp else {
+            return;
+        };
+        let silence = now.saturating_duration_since(last_rtcp);
+        match self.state {
+            SessionState::Established | SessionState::Stale
+                if silence >= self.config.peer_timeout =>
+            {
+                self.set_state(SessionState::Closed, now);
+            }
+            SessionState::Established if silence >= self.config.stale_timeout => {
+                self.set_state(SessionState::Stale, now);
+            }
+            _ => {}
+        }
+    }
+
+    /// Instant the session shall be checked for RTCP silence at.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        let last_rtcp = self.last_rtcp?;
+        match self.state {
+            SessionState::Established => Some(last_rtcp + self.config.stale_timeout),
+            SessionState::Stale => Some(last_rtcp + self.config.peer_timeout),
+            SessionState::Connecting | SessionState::Closed => None,
+        }
+    }
+
+    /// Next state change or peer change, in the order they happened.
+    pub fn poll_event(&mut self) -> Option<SessionEvent> {
+        self.events.pop_front()
+    }
+
+    /// Closes the session for good, RTCP packets received afterwards are ignored.
+    pub fn close(&mut self, now: Instant) {
+        self.closed_locally = true;
+        self.set_state(SessionState::Closed, now);
+    }
+
+    /// Why the session is closed: `RistError::PeerTimeout` if the peer went silent, `None` if the session
+    /// is open or was closed locally.
+    pub fn error(&self) -> Option<RistError> {
+        (self.state == SessionState::Closed && !self.closed_locally)
+            .then_some(RistError::PeerTimeout(self.config.peer_timeout))
+    }
+
+    fn set_state(&mut self, state: SessionState, now: Instant) {
+        if state == self.state {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.state, state);
+        self.events.push_back(SessionEvent::StateChanged {
+            previous,
+            state,
+            at: now,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use risty_proto::rtp::rtcp::sender_report::SenderReport;
+
+    use super::*;
+
+    const PEER: u32 = 0x1234_5678;
+
+    fn session() -> Session {
+        Session::new(
+            Config {
+                stale_timeout: Duration::from_millis(300),
+                peer_timeout: Duration::from_millis(800),
+            },
+            false,
+        )
+    }
+
+    fn report(ssrc: u32) -> Vec<RtcpPacket<'static>> {
+        vec![RtcpPacket::SenderReport(SenderReport::new(ssrc))]
+    }
+
+    fn events(session: &mut Session) -> Vec<SessionEvent> {
+        std::iter::from_fn(|| session.poll_event()).collect()
+    }
+
+    fn state_changed(previous: SessionState, state: SessionState, at: Instant) -> SessionEvent {
+        SessionEvent::StateChanged {
+            previous,
+            state,
+            at,
+        }
+    }
+
+    #[test]
+    fn session_goes_stale_then_times_out() {
+        use SessionState::*;
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let source = "10.0.0.1:5001".parse().unwrap();
+        let mut session = session();
+
+        // Nothing to time out before the peer shows up.
+        assert_eq!(session.state(), SessionState::Connecting);
+        assert_eq!(session.poll_timeout(), None);
+        session.handle_timeout(at(1000));
+        assert_eq!(session.state(), SessionState::Connecting);
+
+        session.on_rtcp(source, &report(PEER), at(1000));
+        assert_eq!(session.poll_timeout(), Some(at(1300)));
+        session.handle_timeout(at(1299));
+        assert_eq!(session.state(), SessionState::Established);
+        session.handle_timeout(at(1300));
+        assert_eq!(session.state(), SessionState::Stale);
+        assert!(session.error().is_none());
+
+        // Back before the peer timeout.
+        session.on_rtcp(source, &report(PEER), at(1400));
+        assert_eq!(session.state(), SessionState::Established);
+
+        session.handle_timeout(at(1700));
+        assert_eq!(session.poll_timeout(), Some(at(2200)));
+        session.handle_timeout(at(2200));
+        assert_eq!(session.state(), SessionState::Closed);
+        assert!(matches!(
+            session.error(),
+            Some(RistError::PeerTimeout(timeout)) if timeout == Duration::from_millis(800)
+        ));
+
+        assert_eq!(
+            events(&mut session),
+            [
+                state_changed(Connecting, Established, at(1000)),
+                state_changed(Established, Stale, at(1300)),
+                state_changed(Stale, Established, at(1400)),
+                state_changed(Established, Stale, at(1700)),
+                state_changed(Stale, Closed, at(2200)),
+            ]
+        );
+    }
+
+    #[test]
+    fn peer_changes_are_reported_once_learnt() {
+        let start = Instant::now();
+        let source = "10.0.0.1:5001".parse().unwrap();
+        let moved = "10.0.0.2:5001".parse().unwrap();
+        let mut session = session();
+
+        session.on_rtcp(source, &report(PEER), start);
+        let learnt = Peer {
+            address: Some(source),
+            ssrc: Some(PEER),
+            cname: None,
+        };
+        assert_eq!(session.peer(), &learnt);
+        assert!(!events(&mut session)
+            .iter()
+            .any(|event| matches!(event, SessionEvent::PeerChanged { .. })));
+
+        // Same peer, nothing to report.
+        session.on_rtcp(source, &report(PEER), start);
+        assert_eq!(events(&mut session), []);
+
+        session.on_rtcp(moved, &report(PEER + 2), start);
+        assert_eq!(
+            events(&mut session),
+            [SessionEvent::PeerChanged {
+                previous: learnt,
+                peer: Peer {
+                    address: Some(moved),
+                    ssrc: Some(PEER + 2),
+                    cname: None,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn closed_locally_without_error() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let source = "10.0.0.1:5001".parse().unwrap();
+        let mut session = session();
+
+        session.on_rtcp(source, &report(PEER), at(0));
+        events(&mut session);
+        session.close(at(100));
+        assert_eq!(session.state(), SessionState::Closed);
+        assert!(session.error().is_none());
+        assert_eq!(
+            events(&mut session),
+            [state_changed(
+                SessionState::Established,
+                SessionState::Closed,
+                at(100)
+            )]
+        );
+
+        // Neither reopened by the peer nor timed out.
+        session.on_rtcp(source, &report(PEER), at(200));
+        session.handle_timeout(at(1000));
+        assert_eq!(session.state(), SessionState::Closed);
+        assert!(session.error().is_none());
+        assert_eq!(events(&mut session), []);
+    }
+
+    #[test]
+    fn timed_out_session_stays_closed() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let source = "10.0.0.1:5001".parse().unwrap();
+        let mut session = session();
+
+        session.on_rtcp(source, &report(PEER), at(0));
+        session.handle_timeout(at(800));
+        assert_eq!(session.state(), SessionState::Closed);
+        assert!(matches!(session.error(), Some(RistError::PeerTimeout(_))));
+
+        session.on_rtcp(source, &report(PEER), at(900));
+        assert_eq!(session.state(), SessionState::Closed);
+        assert_eq!(session.poll_timeout(), None);
+    }
+}
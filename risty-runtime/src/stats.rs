@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+use risty_core::NtpClock;
+use risty_proto::rtp::rtcp::sender_report::SenderInfo;
+
+/// Keeps track of what the sender transmitted, to fill the sender info of SR packets.
+pub struct SenderStats {
+    packet_count: u32,
+    octet_count: u32,
+
+    /// Frequency of the RTP timestamp clock, in Hz.
+    clock_rate: u32,
+
+    /// RTP timestamp of the last media packet and the instant it was sent, used to map wallclock time
+    /// to the RTP timeline.
+    rtp_reference: Option<(Instant, u32)>,
+
+    ntp_clock: NtpClock,
+}
+
+impl SenderStats {
+    pub fn new(clock_rate: u32, ntp_clock: NtpClock) -> Self {
+        Self {
+            packet_count: 0,
+            octet_count: 0,
+            clock_rate,
+            rtp_reference: None,
+            ntp_clock,
+        }
+    }
+
+    /// Shall be called for each original RTP data packet sent, retransmissions are not counted.
+    /// - `payload_size` excludes the RTP header and padding.
+    pub fn on_packet_sent(&mut self, rtp_timestamp: u32, payload_size: usize, now: Instant) {
+        self.packet_count = self.packet_count.wrapping_add(1);
+        self.octet_count = self.octet_count.wrapping_add(payload_size as u32);
+        self.rtp_reference = Some((now, rtp_timestamp));
+    }
+
+    /// The counts shall be reset when the sender changes its SSRC identifier.
+    pub fn reset(&mut self) {
+        self.packet_count = 0;
+        self.octet_count = 0;
+        self.rtp_reference = None;
+    }
+
+    pub fn packet_count(&self) -> u32 {
+        self.packet_count
+    }
+
+    pub fn octet_count(&self) -> u32 {
+        self.octet_count
+    }
+
+    /// RTP timestamp corresponding to `now`, extrapolated from the last packet sent using the clock rate.
+    pub fn rtp_timestamp(&self, now: Instant) -> u32 {
+        match self.rtp_reference {
+            Some((sent_at, rtp_timestamp)) => {
+                let elapsed = now.saturating_duration_since(sent_at);
+                let ticks = elapsed.as_nanos() * self.clock_rate as u128 / 1_000_000_000;
+                rtp_timestamp.wrapping_add(ticks as u32)
+            }
+            None => 0,
+        }
+    }
+
+    /// Snapshot of the statistics at `now`, ready to be put in a SR packet.
+    pub fn sender_info(&self, now: Instant) -> SenderInfo {
+        SenderInfo::new(
+            self.ntp_clock.ntp_time(now),
+            self.rtp_timestamp(now),
+            self.packet_count,
+            self.octet_count,
+        )
+    }
+}
+
+/// Reception statistics of the receiver, for monitoring.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReceiverStats {
+    /// Packets received since the sender started its sequence, including duplicates.
+    pub packets_received: u64,
+
+    /// Highest sequence number received, with the count of sequence number cycles in the upper 16 bits.
+    pub highest_extended_seq_num: u32,
+
+    /// Interarrival jitter, in timestamp units.
+    pub jitter: u32,
+
+    /// Interarrival jitter, converted to a duration using the clock rate.
+    pub jitter_duration: Duration,
+
+    /// Packets lost on the network, before any retransmission.
+    pub packets_lost_before_retransmission: i64,
+
+    /// Packets recovered thanks to retransmissions.
+    pub packets_recovered: u64,
+
+    /// Packets still lost after retransmissions.
+    pub packets_lost: i64,
+}
@@ -0,0 +1,155 @@
+//! Single UDP port mode of the RIST Main Profile. The RTP and RTCP packets of the endpoint are wrapped in GRE
+//! packets in reduced overhead mode, and told apart by their virtual destination port: P for RTP, P+1 for RTCP.
+
+use std::time::{Duration, Instant};
+
+use risty_core::{Marshal, ParseError, Unmarshal};
+use risty_proto::gre::header::ProtocolType;
+use risty_proto::gre::keepalive::KeepAlive;
+use risty_proto::gre::packet::GrePacket;
+use risty_proto::gre::reduced::ReducedOverhead;
+
+use crate::error::{ConfigError, RistError};
+use crate::rtp_sender;
+
+/// Bytes added to each packet by the GRE header and the virtual ports.
+pub const OVERHEAD: usize = 8;
+
+/// Largest payload whose tunnelled packet still fits in a single datagram.
+pub const MAX_PAYLOAD_SIZE: usize = rtp_sender::MAX_PAYLOAD_SIZE - OVERHEAD;
+
+pub struct Config {
+    /// Virtual port of the local end. The peer addresses its RTP packets to it, and its RTCP packets to the
+    /// next one.
+    pub local_port: u16,
+
+    /// Virtual port of the remote end.
+    pub peer_port: u16,
+
+    /// Interval of the keep-alive messages keeping the tunnel open through NATs, `None` disables them.
+    pub keepalive_interval: Option<Duration>,
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // Room is needed for the RTCP port.
+        for port in [self.local_port, self.peer_port] {
+            if port == u16::MAX {
+                return Err(ConfigError::PortOutOfRange(port));
+            }
+        }
+        if self
+            .keepalive_interval
+            .is_some_and(|interval| interval.is_zero())
+        {
+            return Err(ConfigError::Zero {
+                name: "keep-alive interval",
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Packet carried by the tunnel.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inner<'a> {
+    Rtp(&'a [u8]),
+    Rtcp(&'a [u8]),
+    KeepAlive(KeepAlive<'a>),
+}
+
+pub struct Tunnel {
+    config: Config,
+
+    /// Instant the next keep-alive is due, `None` until the first poll.
+    next_keepalive: Option<Instant>,
+}
+
+impl Tunnel {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            next_keepalive: None,
+        }
+    }
+
+    /// Wraps a RTP or RTCP packet to send to the peer.
+    pub fn encapsulate(&self, packet: &[u8], is_rtcp: bool) -> Result<Vec<u8>, RistError> {
+        let offset = is_rtcp as u16;
+        let reduced = ReducedOverhead::new(
+            self.config.local_port + offset,
+            self.config.peer_port + offset,
+            packet,
+        );
+        let mut payload = vec![0; reduced.marshal_size()];
+        reduced.marshal(&mut payload)?;
+        marshal(GrePacket::new(ProtocolType::ReducedOverhead, &payload))
+    }
+
+    /// Unwraps a datagram received from the peer.
+    pub fn decapsulate<'a>(&self, datagram: &'a [u8]) -> Result<Inner<'a>, RistError> {
+        let packet = GrePacket::unmarshal(datagram)?;
+        match packet.protocol_type()? {
+            ProtocolType::ReducedOverhead => {
+                let reduced = ReducedOverhead::unmarshal(packet.payload)?;
+                match reduced
+                    .destination_port
+                    .wrapping_sub(self.config.local_port)
+                {
+                    0 => Ok(Inner::Rtp(reduced.payload)),
+                    1 => Ok(Inner::Rtcp(reduced.payload)),
+                    _ => Err(ParseError::UnexpectedPort(reduced.destination_port).into()),
+                }
+            }
+            ProtocolType::KeepAlive => Ok(Inner::KeepAlive(KeepAlive::unmarshal(packet.payload)?)),
+            // The full datagram mode is not supported, only the reduced overhead one.
+            protocol_type @ (ProtocolType::Ipv4 | ProtocolType::Ipv6) => {
+                Err(ParseError::UnknownProtocolType(protocol_type as u16).into())
+            }
+        }
+    }
+
+    /// Keep-alive message to send if one is due at `now`. The first poll is always due.
+    pub fn poll_keepalive(&mut self, now: Instant) -> Result<Option<Vec<u8>>, RistError> {
+        let Some(interval) = self.config.keepalive_interval else {
+            return Ok(None);
+        };
+        if self.next_keepalive.is_some_and(|next| now < next) {
+            return Ok(None);
+        }
+        self.next_keepalive = Some(now + interval);
+
+        let mut payload = vec![0; KeepAlive::new([0; 6], 0).marshal_size()];
+        KeepAlive::new([0; 6], 0).marshal(&mut payload)?;
+        marshal(GrePacket::new(ProtocolType::KeepAlive, &payload)).map(Some)
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.next_keepalive
+    }
+}
+
+fn marshal(packet: GrePacket) -> Result<Vec<u8>, RistError> {
+    let mut bytes = vec![0; packet.marshal_size()];
+    packet.marshal(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::MAX_DATAGRAM_SIZE;
+
+    #[test]
+    fn largest_packet_fits_in_a_datagram() {
+        let tunnel = Tunnel::new(Config {
+            local_port: 5000,
+            peer_port: 6000,
+            keepalive_interval: None,
+        });
+        // RTP header and largest payload.
+        let packet = vec![0; 12 + MAX_PAYLOAD_SIZE];
+        let datagram = tunnel.encapsulate(&packet, false).unwrap();
+        assert_eq!(datagram.len(), MAX_DATAGRAM_SIZE);
+    }
+}
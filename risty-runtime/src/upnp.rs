@@ -0,0 +1,601 @@
+//! UPnP IGD port mapping, for receivers behind a home or office router. The gateway is discovered with SSDP, then
+//! asked to forward the UDP ports P and P+1 to the receiver. The mappings are renewed until they are dropped, at
+//! which point they are removed from the gateway.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::common::RistListenerPort;
+use crate::error::RistError;
+
+const SSDP_ADDRESS: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+const SEARCH_TARGET: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+
+/// Services able to map ports, the first one listed by the gateway is used.
+const SERVICE_TYPES: [&str; 3] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// Time allowed to each HTTP exchange with the gateway.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Delay before retrying a renewal the gateway failed to answer.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, thiserror::Error)]
+pub enum UpnpError {
+    #[error("no Internet gateway device answered the discovery")]
+    NoGateway,
+
+    #[error("the gateway does not offer a port mapping service")]
+    NoService,
+
+    #[error("the gateway answered with HTTP status {0}")]
+    Http(u16),
+
+    /// UPnP error returned by the gateway, e.g. 718 when the port is already mapped to another host.
+    #[error("the gateway refused the request: {code} {description}")]
+    Soap { code: u16, description: String },
+
+    #[error("malformed answer from the gateway: {0}")]
+    BadResponse(&'static str),
+}
+
+pub struct Config {
+    /// How long to wait for a gateway to answer the discovery.
+    pub discovery_timeout: Duration,
+
+    /// Lease requested for the mappings, they are renewed at half of it. Zero asks for permanent mappings, which
+    /// some gateways refuse. Leases are announced in whole seconds, rounded up.
+    pub lease: Duration,
+
+    /// Description of the mappings shown by the gateway.
+    pub description: String,
+}
+
+/// Port mapping service of an Internet gateway device.
+#[derive(Debug, Clone)]
+pub struct Gateway {
+    /// Address and path the SOAP requests are posted to.
+    control: (SocketAddr, String),
+    service_type: String,
+
+    /// Address of the local host on the network of the gateway, the ports are mapped to it.
+    local_address: IpAddr,
+}
+
+impl Gateway {
+    /// Looks for a gateway on the local network with SSDP, then fetches its description.
+    pub fn discover(timeout: Duration) -> Result<Self, RistError> {
+        Self::discover_at(SSDP_ADDRESS, timeout)
+    }
+
+    /// Like [`Gateway::discover`], with the search sent to `ssdp_address` instead of the SSDP multicast group,
+    /// e.g. straight to a known gateway.
+    pub fn discover_at(ssdp_address: SocketAddr, timeout: Duration) -> Result<Self, RistError> {
+        let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))?;
+        let search = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: {ssdp_address}\r\nMAN: \"ssdp:discover\"\r\nMX: {}\r\nST: {SEARCH_TARGET}\r\n\r\n",
+            timeout.as_secs().max(1)
+        );
+        socket.send_to(search.as_bytes(), ssdp_address)?;
+
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0; 2048];
+        let location = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(UpnpError::NoGateway.into());
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            let len = match socket.recv_from(&mut buf) {
+                Ok((len, _)) => len,
+                Err(err) if is_timeout(&err) => return Err(UpnpError::NoGateway.into()),
+                Err(err) => return Err(err.into()),
+            };
+            let response = String::from_utf8_lossy(&buf[..len]);
+            if let Some(location) = header(&response, "location") {
+                break location.to_string();
+            }
+        };
+
+        let (address, path) = parse_url(&location)?;
+        let description = http(address, "GET", &path, &[], "")?;
+        let (service_type, control_url) = find_service(&description)?;
+        let control = match control_url.strip_prefix("http://") {
+            Some(_) => parse_url(&control_url)?,
+            None if control_url.starts_with('/') => (address, control_url),
+            None => (address, format!("/{control_url}")),
+        };
+
+        // The local address of the route to the gateway, without sending anything.
+        let probe = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))?;
+        probe.connect(control.0)?;
+        Ok(Self {
+            control,
+            service_type,
+            local_address: probe.local_addr()?.ip(),
+        })
+    }
+
+    pub fn local_address(&self) -> IpAddr {
+        self.local_address
+    }
+
+    /// Forwards the UDP port `port` of the gateway to the same port of the local host. `lease` is rounded up to
+    /// whole seconds, so that a short lease is not taken for a permanent one.
+    pub fn add_port_mapping(
+        &self,
+        port: u16,
+        lease: Duration,
+        description: &str,
+    ) -> Result<(), RistError> {
+        self.soap(
+            "AddPortMapping",
+            &format!(
+                "<NewRemoteHost></NewRemoteHost><NewExternalPort>{port}</NewExternalPort>\
+                 <NewProtocol>UDP</NewProtocol><NewInternalPort>{port}</NewInternalPort>\
+                 <NewInternalClient>{}</NewInternalClient><NewEnabled>1</NewEnabled>\
+                 <NewPortMappingDescription>{}</NewPortMappingDescription>\
+                 <NewLeaseDuration>{}</NewLeaseDuration>",
+                self.local_address,
+                escape(description),
+                lease.as_secs() + (lease.subsec_nanos() > 0) as u64
+            ),
+        )
+        .map(|_| ())
+    }
+
+    pub fn delete_port_mapping(&self, port: u16) -> Result<(), RistError> {
+        self.soap(
+            "DeletePortMapping",
+            &format!(
+                "<NewRemoteHost></NewRemoteHost><NewExternalPort>{port}</NewExternalPort>\
+                 <NewProtocol>UDP</NewProtocol>"
+            ),
+        )
+        .map(|_| ())
+    }
+
+    /// Public address of the gateway, the one the sender shall send to.
+    pub fn external_address(&self) -> Result<IpAddr, RistError> {
+        let response = self.soap("GetExternalIPAddress", "")?;
+        element(&response, "NewExternalIPAddress")
+            .and_then(|address| address.trim().parse().ok())
+            .ok_or(UpnpError::BadResponse("no external address").into())
+    }
+
+    fn soap(&self, action: &str, arguments: &str) -> Result<String, RistError> {
+        let body = format!(
+            "<?xml version=\"1.0\"?>\
+             <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+             s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+             <s:Body><u:{action} xmlns:u=\"{}\">{arguments}</u:{action}></s:Body></s:Envelope>",
+            self.service_type
+        );
+        let soap_action = format!("\"{}#{action}\"", self.service_type);
+        let (address, path) = &self.control;
+        http(
+            *address,
+            "POST",
+            path,
+            &[
+                ("Content-Type", "text/xml; charset=\"utf-8\""),
+                ("SOAPAction", &soap_action),
+            ],
+            &body,
+        )
+    }
+}
+
+/// UDP ports P and P+1 forwarded by the gateway. The mappings are renewed by a background thread and removed when
+/// this is dropped, which blocks until the gateway answered.
+pub struct PortMapping {
+    gateway: Gateway,
+    ports: [u16; 2],
+
+    /// Set to stop the renewal thread.
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    renewal: Option<JoinHandle<()>>,
+}
+
+impl PortMapping {
+    /// Discovers the gateway and maps P and P+1.
+    pub fn map(listen_port: RistListenerPort, config: Config) -> Result<Self, RistError> {
+        let gateway = Gateway::discover(config.discovery_timeout)?;
+        Self::map_on(gateway, listen_port, config)
+    }
+
+    /// Maps P and P+1 on an already discovered gateway.
+    pub fn map_on(
+        gateway: Gateway,
+        listen_port: RistListenerPort,
+        config: Config,
+    ) -> Result<Self, RistError> {
+        let ports = [listen_port.get(), listen_port.get() + 1];
+        for (index, port) in ports.into_iter().enumerate() {
+            if let Err(err) = gateway.add_port_mapping(port, config.lease, &config.description) {
+                // Half a mapping is of no use to the receiver.
+                for mapped in &ports[..index] {
+                    let _ = gateway.delete_port_mapping(*mapped);
+                }
+                return Err(err);
+            }
+        }
+
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let renewal = {
+            let gateway = gateway.clone();
+            let stop = stop.clone();
+            thread::Builder::new()
+                .name("risty-upnp".to_string())
+                .spawn(move || renew(&gateway, ports, &config, &stop))?
+        };
+
+        Ok(Self {
+            gateway,
+            ports,
+            stop,
+            renewal: Some(renewal),
+        })
+    }
+
+    pub fn gateway(&self) -> &Gateway {
+        &self.gateway
+    }
+
+    pub fn ports(&self) -> [u16; 2] {
+        self.ports
+    }
+}
+
+impl Drop for PortMapping {
+    fn drop(&mut self) {
+        let (stopped, wakeup) = &*self.stop;
+        *stopped.lock().unwrap() = true;
+        wakeup.notify_one();
+        if let Some(renewal) = self.renewal.take() {
+            let _ = renewal.join();
+        }
+        for port in self.ports {
+            // The lease expires anyway if the gateway cannot be reached.
+            let _ = self.gateway.delete_port_mapping(port);
+        }
+    }
+}
+
+/// Renews the mappings at half of the lease until stopped. Permanent mappings are left alone.
+fn renew(gateway: &Gateway, ports: [u16; 2], config: &Config, stop: &(Mutex<bool>, Condvar)) {
+    if config.lease.is_zero() {
+        return;
+    }
+    let (stopped, wakeup) = stop;
+    let mut delay = config.lease / 2;
+    let mut guard = stopped.lock().unwrap();
+    loop {
+        guard = wakeup
+            .wait_timeout_while(guard, delay, |stopped| !*stopped)
+            .unwrap()
+            .0;
+        if *guard {
+            return;
+        }
+        let renewed = ports.iter().all(|port| {
+            gateway
+                .add_port_mapping(*port, config.lease, &config.description)
+                .is_ok()
+        });
+        delay = match renewed {
+            true => config.lease / 2,
+            false => RETRY_DELAY.min(config.lease / 2),
+        };
+    }
+}
+
+/// Sends an HTTP/1.1 request and returns the body of a successful response.
+fn http(
+    address: SocketAddr,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Result<String, RistError> {
+    let mut stream = TcpStream::connect_timeout(&address, HTTP_TIMEOUT)?;
+    stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+    stream.set_write_timeout(Some(HTTP_TIMEOUT))?;
+
+    let mut request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {address}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or(UpnpError::BadResponse("truncated HTTP response"))?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or(UpnpError::BadResponse("no HTTP status"))?;
+    let body = match header(head, "transfer-encoding") {
+        Some(encoding) if encoding.eq_ignore_ascii_case("chunked") => dechunk(body)?,
+        _ => body.to_string(),
+    };
+
+    match status {
+        200..=299 => Ok(body),
+        // SOAP faults carry the UPnP error code.
+        500 => Err(UpnpError::Soap {
+            code: element(&body, "errorCode")
+                .and_then(|code| code.trim().parse().ok())
+                .unwrap_or_default(),
+            description: element(&body, "errorDescription")
+                .unwrap_or_default()
+                .to_string(),
+        }
+        .into()),
+        _ => Err(UpnpError::Http(status).into()),
+    }
+}
+
+fn dechunk(mut body: &str) -> Result<String, RistError> {
+    let mut decoded = String::new();
+    loop {
+        let (size, rest) = body
+            .split_once("\r\n")
+            .ok_or(UpnpError::BadResponse("truncated chunk"))?;
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16)
+            .map_err(|_| UpnpError::BadResponse("bad chunk size"))?;
+        if size == 0 {
+            return Ok(decoded);
+        }
+        let chunk = rest
+            .get(..size)
+            .ok_or(UpnpError::BadResponse("truncated chunk"))?;
+        decoded.push_str(chunk);
+        body = rest[size..].trim_start_matches("\r\n");
+    }
+}
+
+/// Value of a header, the name is case-insensitive.
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Text of the first `<name>` element, ignoring any namespace prefix.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("{name}>"))? + name.len() + 1;
+    let end = start + xml[start..].find("</")?;
+    Some(&xml[start..end])
+}
+
+/// Service type and control URL of the first port mapping service of a device description.
+fn find_service(description: &str) -> Result<(String, String), RistError> {
+    description
+        .split("<service>")
+        .skip(1)
+        .find_map(|service| {
+            let service_type = element(service, "serviceType")?.trim();
+            if !SERVICE_TYPES.contains(&service_type) {
+                return None;
+            }
+            let control_url = element(service, "controlURL")?.trim();
+            Some((service_type.to_string(), control_url.to_string()))
+        })
+        .ok_or(UpnpError::NoService.into())
+}
+
+/// Address and path of an `http://host:port/path` URL.
+fn parse_url(url: &str) -> Result<(SocketAddr, String), RistError> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or(UpnpError::BadResponse("not an HTTP URL"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let authority = match authority.contains(':') {
+        true => authority.to_string(),
+        false => format!("{authority}:80"),
+    };
+    let address = authority
+        .to_socket_addrs()?
+        .next()
+        .ok_or(UpnpError::BadResponse("unresolved host"))?;
+    Ok((address, path.to_string()))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn is_timeout(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+
+    use super::*;
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    /// Action received by the mock gateway, with the external port it is about and the lease requested.
+    type Action = (String, u16, Option<u64>);
+
+    /// In-process IGD answering the SSDP search on a unicast UDP socket and the SOAP requests on a TCP
+    /// listener. Each action is reported before it is answered. AddPortMapping requests for `refused_port`
+    /// fail with the UPnP error 718.
+    fn mock_gateway(refused_port: Option<u16>) -> (SocketAddr, Receiver<Action>) {
+        let ssdp = UdpSocket::bind(SocketAddr::new(LOCALHOST, 0)).unwrap();
+        let ssdp_address = ssdp.local_addr().unwrap();
+        let http = TcpListener::bind(SocketAddr::new(LOCALHOST, 0)).unwrap();
+        let http_address = http.local_addr().unwrap();
+        let (actions, log) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut buf = [0; 2048];
+            while let Ok((_, source)) = ssdp.recv_from(&mut buf) {
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nST: {SEARCH_TARGET}\r\nLOCATION: http://{http_address}/description.xml\r\n\r\n"
+                );
+                ssdp.send_to(response.as_bytes(), source).unwrap();
+            }
+        });
+
+        thread::spawn(move || {
+            for stream in http.incoming() {
+                let mut stream = stream.unwrap();
+                let (action, body) = read_request(&stream);
+                let (status, body) = match action.as_deref() {
+                    None => ("200 OK", DESCRIPTION.to_string()),
+                    Some(action) => {
+                        let port = element(&body, "NewExternalPort")
+                            .and_then(|port| port.parse().ok())
+                            .unwrap_or_default();
+                        let lease =
+                            element(&body, "NewLeaseDuration").and_then(|lease| lease.parse().ok());
+                        let _ = actions.send((action.to_string(), port, lease));
+                        match action == "AddPortMapping" && Some(port) == refused_port {
+                            true => ("500 Internal Server Error", FAULT.to_string()),
+                            false => ("200 OK", String::new()),
+                        }
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        (ssdp_address, log)
+    }
+
+    /// SOAP action, `None` for a GET, and body of an HTTP request.
+    fn read_request(stream: &TcpStream) -> (Option<String>, String) {
+        let mut reader = std::io::BufReader::new(stream);
+        let mut head = String::new();
+        while !head.ends_with("\r\n\r\n") {
+            reader.read_line(&mut head).unwrap();
+        }
+        let length = header(&head, "content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        let action = header(&head, "soapaction")
+            .and_then(|action| action.trim_matches('"').split_once('#'))
+            .map(|(_, action)| action.to_string());
+        (action, String::from_utf8(body).unwrap())
+    }
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root><device><serviceList>\
+        <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>\
+        <controlURL>/l3f</controlURL></service>\
+        <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+        <controlURL>/ctl/IPConn</controlURL></service>\
+        </serviceList></device></root>";
+
+    const FAULT: &str = "<s:Envelope><s:Body><s:Fault><detail><UPnPError>\
+        <errorCode>718</errorCode><errorDescription>ConflictInMappingEntry</errorDescription>\
+        </UPnPError></detail></s:Fault></s:Body></s:Envelope>";
+
+    fn config(lease: Duration) -> Config {
+        Config {
+            discovery_timeout: Duration::from_secs(1),
+            lease,
+            description: "risty".to_string(),
+        }
+    }
+
+    /// Next action received by the gateway, waiting for it.
+    fn next(log: &Receiver<Action>) -> Action {
+        log.recv_timeout(Duration::from_secs(5)).unwrap()
+    }
+
+    fn add(port: u16, lease: u64) -> Action {
+        ("AddPortMapping".to_string(), port, Some(lease))
+    }
+
+    fn delete(port: u16) -> Action {
+        ("DeletePortMapping".to_string(), port, None)
+    }
+
+    #[test]
+    fn maps_renews_and_deletes() {
+        let (ssdp_address, log) = mock_gateway(None);
+        let gateway = Gateway::discover_at(ssdp_address, Duration::from_secs(1)).unwrap();
+        assert_eq!(gateway.local_address(), LOCALHOST);
+
+        let listen_port = RistListenerPort::new(6000).unwrap();
+        let mapping =
+            PortMapping::map_on(gateway, listen_port, config(Duration::from_secs(1))).unwrap();
+        assert_eq!(mapping.ports(), [6000, 6001]);
+        assert_eq!(next(&log), add(6000, 1));
+        assert_eq!(next(&log), add(6001, 1));
+
+        // Renewed at half of the lease.
+        assert_eq!(next(&log), add(6000, 1));
+        assert_eq!(next(&log), add(6001, 1));
+
+        drop(mapping);
+        let actions = log.try_iter().collect::<Vec<_>>();
+        assert_eq!(actions[actions.len() - 2..], [delete(6000), delete(6001)]);
+    }
+
+    #[test]
+    fn short_lease_is_not_permanent() {
+        let (ssdp_address, log) = mock_gateway(None);
+        let gateway = Gateway::discover_at(ssdp_address, Duration::from_secs(1)).unwrap();
+
+        gateway
+            .add_port_mapping(6000, Duration::from_millis(500), "risty")
+            .unwrap();
+        assert_eq!(next(&log), add(6000, 1));
+    }
+
+    #[test]
+    fn partial_mapping_is_removed() {
+        let (ssdp_address, log) = mock_gateway(Some(6001));
+        let gateway = Gateway::discover_at(ssdp_address, Duration::from_secs(1)).unwrap();
+
+        let listen_port = RistListenerPort::new(6000).unwrap();
+        let result = PortMapping::map_on(gateway, listen_port, config(Duration::from_secs(60)));
+        assert!(matches!(
+            result,
+            Err(RistError::Upnp(UpnpError::Soap { code: 718, .. }))
+        ));
+        assert_eq!(
+            log.try_iter().collect::<Vec<_>>(),
+            [add(6000, 60), add(6001, 60), delete(6000)]
+        );
+    }
+}